@@ -1,8 +1,9 @@
 use core::fmt;
-use std::{
-    cmp::Reverse,
-    collections::{BinaryHeap, HashSet, VecDeque},
-};
+use itertools::Itertools;
+use render::Renderer;
+use std::collections::{HashSet, VecDeque};
+
+mod render;
 
 static EXAMPLE_INPUT: &str = r#"
 ..........
@@ -16,15 +17,73 @@ static EXAMPLE_INPUT: &str = r#"
 ..........
 "#;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    fn opposite(self) -> Self {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+
+    /// The position one step away in this direction, if it doesn't fall off
+    /// the top or left edge.
+    fn step(self, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        match self {
+            Direction::North => Some((x, y.checked_sub(1)?)),
+            Direction::East => Some((x + 1, y)),
+            Direction::South => Some((x, y + 1)),
+            Direction::West => Some((x.checked_sub(1)?, y)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StartError {
+    Missing,
+    /// No pair of neighbours connects back to `S` and closes a loop.
+    NoShape,
+    /// More than one pipe shape would close a loop through `S`.
+    Ambiguous(Vec<Tile>),
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StartError::Missing => write!(f, "the map has no start tile"),
+            StartError::NoShape => write!(f, "no pipe shape for S closes a loop"),
+            StartError::Ambiguous(shapes) => {
+                write!(f, "S could be any of {}", shapes.iter().join(", "))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 struct Map {
-    start: (usize, usize),
+    start: Option<(usize, usize)>,
     tiles: Vec<Vec<Tile>>,
 }
 
 impl Map {
     fn new(input: &str) -> Self {
-        let mut start = (0, 0);
+        let mut start = None;
         let mut tiles = Vec::new();
 
         for line in input.trim().lines() {
@@ -33,7 +92,7 @@ impl Map {
             for c in line.chars() {
                 row.push(Tile::from(c));
                 if c == 'S' {
-                    start = (row.len() - 1, tiles.len());
+                    start = Some((row.len() - 1, tiles.len()));
                 }
             }
 
@@ -51,6 +110,168 @@ impl Map {
         self.tiles.get_mut(y).and_then(|row| row.get_mut(x))
     }
 
+    /// Works out which pipe is hidden under `S` and puts it on the map.
+    ///
+    /// Every pair of neighbours that connects back to `S` is a candidate
+    /// shape; a candidate is only valid if following the pipes out of one
+    /// end leads back in through the other.
+    fn infer_start(&mut self) -> Result<Tile, StartError> {
+        let start = self.start.ok_or(StartError::Missing)?;
+
+        let connected = Direction::ALL
+            .into_iter()
+            .filter(|&dir| {
+                dir.step(start)
+                    .and_then(|(x, y)| self.get(x, y))
+                    .is_some_and(|tile| tile.connects(dir.opposite()))
+            })
+            .collect::<Vec<_>>();
+
+        let shapes = connected
+            .iter()
+            .tuple_combinations()
+            .filter(|&(&a, &b)| self.closes_loop(start, a, b))
+            .map(|(&a, &b)| Tile::default().with(a).with(b))
+            .collect::<Vec<_>>();
+
+        let shape = match shapes.as_slice() {
+            [] => return Err(StartError::NoShape),
+            [shape] => *shape,
+            _ => return Err(StartError::Ambiguous(shapes)),
+        };
+        *self.get_mut(start.0, start.1).unwrap() = shape;
+        Ok(shape)
+    }
+
+    /// Follows the pipes leaving `start` towards `out`, returning whether
+    /// they come back to `start` from the `back` direction.
+    fn closes_loop(&self, start: (usize, usize), out: Direction, back: Direction) -> bool {
+        let mut pos = start;
+        let mut dir = out;
+        // a loop can't be longer than the map
+        for _ in 0..self.tiles.iter().map(Vec::len).sum::<usize>() {
+            let Some(next) = dir.step(pos) else {
+                return false;
+            };
+            if next == start {
+                return dir == back.opposite();
+            }
+            let Some(tile) = self.get(next.0, next.1) else {
+                return false;
+            };
+            if !tile.connects(dir.opposite()) {
+                return false;
+            }
+            match Direction::ALL
+                .into_iter()
+                .find(|&d| d != dir.opposite() && tile.connects(d))
+            {
+                Some(d) => dir = d,
+                None => return false,
+            }
+            pos = next;
+        }
+        false
+    }
+
+    /// The tiles of the loop through `S`, in the order they're walked.
+    fn main_loop(&self) -> Vec<(usize, usize)> {
+        let start = self.start.expect("map should have a start tile");
+        let mut dir = Direction::ALL
+            .into_iter()
+            .find(|&d| self.get(start.0, start.1).unwrap().connects(d))
+            .expect("start tile should be a pipe");
+
+        let mut main_loop = vec![start];
+        let mut pos = dir.step(start).unwrap();
+        while pos != start {
+            main_loop.push(pos);
+            let tile = self.get(pos.0, pos.1).unwrap();
+            dir = Direction::ALL
+                .into_iter()
+                .find(|&d| d != dir.opposite() && tile.connects(d))
+                .unwrap();
+            pos = dir.step(pos).unwrap();
+        }
+        main_loop
+    }
+
+    /// Counts the enclosed tiles row by row: a tile is inside if the loop
+    /// crosses the row an odd number of times to its left. Only loop tiles
+    /// with a northward pipe count as crossings, so `L-J` turns back the way
+    /// it came while `L-7` crosses once.
+    fn enclosed_by_scanline(&self, main_loop: &[(usize, usize)]) -> usize {
+        self.scanline_inside(main_loop).len()
+    }
+
+    fn scanline_inside(&self, main_loop: &[(usize, usize)]) -> HashSet<(usize, usize)> {
+        let on_loop = main_loop.iter().copied().collect::<HashSet<_>>();
+        let mut inside_tiles = HashSet::new();
+        for (y, row) in self.tiles.iter().enumerate() {
+            let mut inside = false;
+            for (x, tile) in row.iter().enumerate() {
+                if on_loop.contains(&(x, y)) {
+                    inside ^= tile.north;
+                } else if inside {
+                    inside_tiles.insert((x, y));
+                }
+            }
+        }
+        inside_tiles
+    }
+
+    /// Counts the enclosed tiles by expanding the map so there are gaps
+    /// between parallel pipes, then flooding in from every empty tile on
+    /// the border.
+    fn enclosed_by_flood(&self, main_loop: &[(usize, usize)]) -> usize {
+        // clear everything except the loop
+        let on_loop = main_loop.iter().copied().collect::<HashSet<_>>();
+        let mut map = self.clone();
+        for (y, row) in map.tiles.iter_mut().enumerate() {
+            for (x, tile) in row.iter_mut().enumerate() {
+                if !on_loop.contains(&(x, y)) {
+                    *tile = Tile::from('.');
+                }
+            }
+        }
+        map.expand();
+
+        let (width, height) = (map.tiles[0].len(), map.tiles.len());
+        let mut queue = (0..width)
+            .flat_map(|x| [(x, 0), (x, height - 1)])
+            .chain((0..height).flat_map(|y| [(0, y), (width - 1, y)]))
+            .collect::<VecDeque<_>>();
+
+        let mut n_outside = 0;
+        while let Some((x, y)) = queue.pop_front() {
+            if map.get(x, y).unwrap() != &Tile::from('.') {
+                continue;
+            }
+
+            let tile = map.get_mut(x, y).unwrap();
+            tile.outside = true;
+            if x % 2 == 0 && y % 2 == 0 {
+                n_outside += 1;
+            }
+
+            // check neighbors
+            if y > 0 {
+                queue.push_back((x, y - 1));
+            }
+            if x < width - 1 {
+                queue.push_back((x + 1, y));
+            }
+            if y < height - 1 {
+                queue.push_back((x, y + 1));
+            }
+            if x > 0 {
+                queue.push_back((x - 1, y));
+            }
+        }
+
+        self.tiles.len() * self.tiles[0].len() - n_outside - main_loop.len()
+    }
+
     fn expand(&mut self) {
         self.tiles = self
             .tiles
@@ -91,6 +312,25 @@ struct Tile {
 }
 
 impl Tile {
+    fn connects(&self, dir: Direction) -> bool {
+        match dir {
+            Direction::North => self.north,
+            Direction::East => self.east,
+            Direction::South => self.south,
+            Direction::West => self.west,
+        }
+    }
+
+    fn with(mut self, dir: Direction) -> Self {
+        match dir {
+            Direction::North => self.north = true,
+            Direction::East => self.east = true,
+            Direction::South => self.south = true,
+            Direction::West => self.west = true,
+        }
+        self
+    }
+
     fn expand(&self) -> [[Tile; 2]; 2] {
         [
             [
@@ -160,109 +400,55 @@ impl From<char> for Tile {
 fn main() {
     println!("\n-- Advent of Code 2023 - Day 10 --");
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
-
-    part1(input);
-    // part2(input);
-}
-
-fn part1(input: &str) {
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
     let mut map = Map::new(input);
-
-    let start_pos = map.start;
-    println!("{:?}", start_pos);
-    // let start = Tile::from('F'); // hardcoded for example input
-    let start = Tile::from('|'); // hardcoded for real input
-    let start_tile = map.get_mut(start_pos.0, start_pos.1).unwrap();
-    *start_tile = start;
-    // println!("{}", map);
-
-    let mut distance = 0;
-    let mut visited: HashSet<(usize, usize)> = HashSet::new();
-
-    let mut priority_queue = BinaryHeap::new();
-    priority_queue.push((0, start_pos));
-    while let Some((d, (x, y))) = priority_queue.pop() {
-        if visited.contains(&(x, y)) {
-            continue;
-        }
-        visited.insert((x, y));
-        distance = distance.max(d);
-
-        // check neighbors
-        let tile = map.get(x, y).unwrap();
-        if tile.north {
-            priority_queue.push(((d + 1), (x, y - 1)));
-        }
-        if tile.east {
-            priority_queue.push(((d + 1), (x + 1, y)));
-        }
-        if tile.south {
-            priority_queue.push(((d + 1), (x, y + 1)));
-        }
-        if tile.west {
-            priority_queue.push(((d + 1), (x - 1, y)));
-        }
+    let start = match map.infer_start() {
+        Ok(start) => start,
+        Err(e) => panic!("Couldn't place the start tile: {}", e),
+    };
+    println!("Start {:?} is {}", map.start.unwrap(), start);
+
+    let main_loop = map.main_loop();
+    part1(&main_loop);
+    part2(&map, &main_loop);
+
+    if std::env::args().any(|a| a == "--render") {
+        print!("{}", Renderer::new(&map, &main_loop));
     }
+}
 
-    println!(
-        "Part 1: {}",
-        distance / 2 + if distance % 2 == 0 { 0 } else { 1 }
-    );
-
-    // clear everything except the loop
-    for i in 0..map.tiles.len() {
-        for j in 0..map.tiles[i].len() {
-            if !visited.contains(&(j, i)) {
-                *map.get_mut(j, i).unwrap() = Tile::from('.');
-            }
-        }
-    }
-    // println!("{}", map);
-
-    // expand tiles
-    let mut expanded_map = map.clone();
-    expanded_map.expand();
-    // println!("{}", expanded_map);
-
-    let mut queue: VecDeque<(usize, usize)> = VecDeque::new();
-    queue.push_back((0, 0)); // might not work for all inputs
-
-    let mut n_outside = 0;
-    while let Some((x, y)) = queue.pop_front() {
-        if expanded_map.get(x, y).unwrap() != &Tile::from('.') {
-            continue;
-        }
-
-        let tile = expanded_map.get_mut(x, y).unwrap();
-        tile.outside = true;
-        if x % 2 == 0 && y % 2 == 0 {
-            n_outside += 1;
-        }
+fn part1(main_loop: &[(usize, usize)]) {
+    println!("Part 1: {}", main_loop.len() / 2);
+}
 
-        // check neighbors
-        if y > 0 {
-            queue.push_back((x, y - 1));
-        }
-        if x < expanded_map.tiles[y].len() - 1 {
-            queue.push_back((x + 1, y));
-        }
-        if y < expanded_map.tiles.len() - 1 {
-            queue.push_back((x, y + 1));
-        }
-        if x > 0 {
-            queue.push_back((x - 1, y));
-        }
+fn part2(map: &Map, main_loop: &[(usize, usize)]) {
+    let shoelace = enclosed_by_shoelace(main_loop);
+    let scanline = map.enclosed_by_scanline(main_loop);
+    let flood = map.enclosed_by_flood(main_loop);
+    // shoelace only needs the loop itself, so it's the answer either way
+    println!("Part 2: {}", shoelace);
+    if shoelace != scanline || shoelace != flood {
+        println!(
+            "Warning: enclosed tile counts disagree: shoelace {}, scanline {}, flood fill {}",
+            shoelace, scanline, flood
+        );
     }
-    // println!("{}", expanded_map);
-
-    let n_inside = map.tiles.len() * map.tiles[0].len() - n_outside - visited.len();
-    println!("Part 2: {}", n_inside);
 }
 
-fn _part2(_input: &str) {
-    todo!()
+/// Counts the tiles enclosed by the loop using the shoelace formula for its
+/// area and Pick's theorem (`A = i + b/2 - 1`) to get the interior points.
+fn enclosed_by_shoelace(main_loop: &[(usize, usize)]) -> usize {
+    let twice_area = main_loop
+        .iter()
+        .circular_tuple_windows()
+        .map(|(&(x1, y1), &(x2, y2))| (x1 * y2) as i64 - (x2 * y1) as i64)
+        .sum::<i64>()
+        .unsigned_abs() as usize;
+    (twice_area + 2 - main_loop.len()) / 2
 }
 
 impl fmt::Display for Tile {
@@ -304,4 +490,61 @@ L-J
         println!("{:?}", map);
         println!("{}", map);
     }
+
+    #[test]
+    fn test_enclosed() {
+        let count = |input: &str| {
+            let mut map = Map::new(input);
+            map.infer_start().unwrap();
+            let main_loop = map.main_loop();
+            [
+                enclosed_by_shoelace(&main_loop),
+                map.enclosed_by_scanline(&main_loop),
+                map.enclosed_by_flood(&main_loop),
+            ]
+        };
+
+        assert_eq!(count(EXAMPLE_INPUT), [4; 3]);
+        // the loop runs through the top-left corner
+        assert_eq!(count("S--7\n|..|\nL--J"), [2; 3]);
+        assert_eq!(
+            count(
+                r#"
+FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L
+"#
+            ),
+            [10; 3]
+        );
+    }
+
+    #[test]
+    fn test_infer_start() {
+        let mut map = Map::new(EXAMPLE_INPUT);
+        assert_eq!(map.infer_start(), Ok(Tile::from('F')));
+
+        // a figure of eight where both `J` and `F` close a loop through S
+        let mut map = Map::new(
+            r#"
+F-7..
+|.|..
+L-S-7
+..|.|
+..L-J
+"#,
+        );
+        assert!(matches!(map.infer_start(), Err(StartError::Ambiguous(_))));
+
+        let mut map = Map::new("...\n.S-\n...");
+        assert_eq!(map.infer_start(), Err(StartError::NoShape));
+        assert_eq!(Map::new("F7\nLJ").infer_start(), Err(StartError::Missing));
+    }
 }
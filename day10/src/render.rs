@@ -0,0 +1,69 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{Map, Tile};
+
+const RESET: &str = "\x1b[0m";
+const LOOP: &str = "\x1b[1;37m";
+const START: &str = "\x1b[1;35m";
+const INSIDE: &str = "\x1b[32m";
+const OUTSIDE: &str = "\x1b[90m";
+
+/// ANSI colored view of a pipe map, with the main loop drawn in box-drawing
+/// characters.
+///
+/// The loop is bold white with `S` picked out in bold magenta. Tiles the
+/// loop encloses are green (empty ones drawn as `▪`) and everything outside
+/// it grey; pipes that aren't part of the loop keep their shape so the junk
+/// is still visible.
+pub struct Renderer<'a> {
+    map: &'a Map,
+    on_loop: HashSet<(usize, usize)>,
+    inside: HashSet<(usize, usize)>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(map: &'a Map, main_loop: &[(usize, usize)]) -> Self {
+        Self {
+            map,
+            on_loop: main_loop.iter().copied().collect(),
+            inside: map.scanline_inside(main_loop),
+        }
+    }
+}
+
+fn glyph(tile: &Tile) -> char {
+    match (tile.north, tile.east, tile.south, tile.west) {
+        (false, true, false, true) => '═',
+        (true, false, true, false) => '║',
+        (true, true, false, false) => '╚',
+        (true, false, false, true) => '╝',
+        (false, true, true, false) => '╔',
+        (false, false, true, true) => '╗',
+        _ => '·',
+    }
+}
+
+impl fmt::Display for Renderer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (y, row) in self.map.tiles.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                let color = if self.map.start == Some((x, y)) {
+                    START
+                } else if self.on_loop.contains(&(x, y)) {
+                    LOOP
+                } else if self.inside.contains(&(x, y)) {
+                    INSIDE
+                } else {
+                    OUTSIDE
+                };
+                let c = match glyph(tile) {
+                    '·' if color == INSIDE => '▪',
+                    c => c,
+                };
+                write!(f, "{}{}", color, c)?;
+            }
+            writeln!(f, "{}", RESET)?;
+        }
+        Ok(())
+    }
+}
@@ -11,6 +11,30 @@ static EXAMPLE_INPUT: &str = r#"
 #...#.....
 "#;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+}
+
+impl Metric {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "manhattan" => Some(Metric::Manhattan),
+            "chebyshev" => Some(Metric::Chebyshev),
+            _ => None,
+        }
+    }
+
+    fn distance(self, (x1, y1): (usize, usize), (x2, y2): (usize, usize)) -> usize {
+        let (dx, dy) = (x1.abs_diff(x2), y1.abs_diff(y2));
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct SpaceGrid {
     galaxies: Vec<(usize, usize)>,
@@ -55,78 +79,218 @@ impl SpaceGrid {
         }
     }
 
-    // e.g. walk from (3, 0) to (7, 1) should be 6 steps
-    fn walk_distance_between(
+    /// Galaxy positions once every empty row and column has been replaced
+    /// by `factor` of them.
+    fn expanded_galaxies(&self, factor: usize) -> Vec<(usize, usize)> {
+        // the number of empty rows/cols before `i`, found by binary search
+        let expand =
+            |empties: &[usize], i: usize| i + empties.partition_point(|&e| e < i) * (factor - 1);
+        self.galaxies
+            .iter()
+            .map(|&(x, y)| (expand(&self.empty_cols, x), expand(&self.empty_rows, y)))
+            .collect()
+    }
+
+    /// Sum of the shortest paths between every pair of galaxies.
+    ///
+    /// Manhattan distance splits into independent x and y parts, so each
+    /// axis is sorted and the `i`th coordinate contributes `i * v - (sum of
+    /// the i before it)`, making the whole thing O(n log n).
+    fn total_distance(&self, factor: usize) -> usize {
+        let (xs, ys): (Vec<_>, Vec<_>) = self.expanded_galaxies(factor).into_iter().unzip();
+        axis_total(xs) + axis_total(ys)
+    }
+
+    /// The `k` galaxies closest to `galaxy`, nearest first, as (index,
+    /// distance) pairs.
+    fn nearest(
         &self,
-        start: (usize, usize),
-        end: (usize, usize),
-        n_expand: usize,
-    ) -> usize {
-        let mut distance = 0;
-        let mut current = start;
-
-        // if an empty row or col is passed, count it n_expand times
-        loop {
-            let (x, y) = current;
-            let next = if x < end.0 {
-                (x + 1, y)
-            } else if x > end.0 {
-                (x - 1, y)
-            } else if y < end.1 {
-                (x, y + 1)
-            } else if y > end.1 {
-                (x, y - 1)
-            } else {
-                break;
-            };
-
-            if self.empty_rows.contains(&y) || self.empty_cols.contains(&x) {
-                distance += n_expand;
-            } else {
-                distance += 1;
-            }
+        galaxy: usize,
+        k: usize,
+        factor: usize,
+        metric: Metric,
+    ) -> Vec<(usize, usize)> {
+        let galaxies = self.expanded_galaxies(factor);
+        let mut distances = galaxies
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != galaxy)
+            .map(|(i, &other)| (i, metric.distance(galaxies[galaxy], other)))
+            .collect::<Vec<_>>();
+        distances.sort_by_key(|&(i, d)| (d, i));
+        distances.truncate(k);
+        distances
+    }
+
+    /// The two galaxies furthest apart and the distance between them.
+    ///
+    /// Under Chebyshev the answer is whichever axis has the widest spread.
+    /// Manhattan distance is Chebyshev distance after rotating by 45° (`x +
+    /// y`, `x - y`), so both are found in a single pass.
+    fn farthest_pair(&self, factor: usize, metric: Metric) -> Option<(usize, usize, usize)> {
+        let galaxies = self.expanded_galaxies(factor);
+        let coords = galaxies
+            .iter()
+            .map(|&(x, y)| match metric {
+                Metric::Manhattan => [(x + y) as i64, x as i64 - y as i64],
+                Metric::Chebyshev => [x as i64, y as i64],
+            })
+            .collect::<Vec<_>>();
 
-            current = next;
+        (0..2)
+            .filter_map(|axis| {
+                let lo = (0..coords.len()).min_by_key(|&i| coords[i][axis])?;
+                let hi = (0..coords.len()).max_by_key(|&i| coords[i][axis])?;
+                (lo != hi).then(|| (lo.min(hi), lo.max(hi)))
+            })
+            .map(|(a, b)| (a, b, metric.distance(galaxies[a], galaxies[b])))
+            .max_by_key(|&(a, b, d)| (d, std::cmp::Reverse((a, b))))
+    }
+
+    /// Distances between every pair of galaxies as CSV, with galaxies
+    /// numbered from 1 as in the puzzle.
+    fn distance_matrix_csv(&self, factor: usize, metric: Metric) -> String {
+        let galaxies = self.expanded_galaxies(factor);
+        let mut csv = String::from("galaxy");
+        for i in 1..=galaxies.len() {
+            csv += &format!(",{}", i);
+        }
+        csv.push('\n');
+
+        for (i, &a) in galaxies.iter().enumerate() {
+            csv += &(i + 1).to_string();
+            for &b in &galaxies {
+                csv += &format!(",{}", metric.distance(a, b));
+            }
+            csv.push('\n');
         }
+        csv
+    }
+}
 
-        distance
+fn axis_total(mut values: Vec<usize>) -> usize {
+    values.sort_unstable();
+    let mut prefix = 0;
+    let mut total = 0;
+    for (i, v) in values.into_iter().enumerate() {
+        total += i * v - prefix;
+        prefix += v;
     }
+    total
 }
 
 fn main() {
     println!("\n-- Advent of Code 2023 - Day 11 --");
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
-
-    part1(input);
-    // part2(input);
-}
-
-fn part1(input: &str) {
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
     let grid = SpaceGrid::new(input);
 
     println!("Empty cols: {:?}", grid.empty_cols);
     println!("Empty rows: {:?}", grid.empty_rows);
-    // println!("Galaxies: {:?}", grid.galaxies);
-
-    // calc distance between each pair of galaxies
-    let mut part1_distances = Vec::new();
-    let mut part2_distances = Vec::new();
-    for (i, galaxy) in grid.galaxies.iter().enumerate() {
-        for other_galaxy in &grid.galaxies[i + 1..] {
-            let p1_distance = grid.walk_distance_between(*galaxy, *other_galaxy, 2);
-            let p2_distance = grid.walk_distance_between(*galaxy, *other_galaxy, 1_000_000);
-
-            part1_distances.push(p1_distance);
-            part2_distances.push(p2_distance);
+
+    part1(&grid);
+    part2(&grid);
+
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg = |flag: &str, n: usize| args.iter().skip_while(|a| *a != flag).nth(n);
+
+    let factor = match arg("--expansion", 1) {
+        Some(factor) => {
+            let factor = factor.parse().expect("expansion should be a number");
+            if factor == 0 {
+                panic!("expansion must be at least 1");
+            }
+            println!("Expansion {}: {}", factor, grid.total_distance(factor));
+            factor
+        }
+        None => 2,
+    };
+    let metric = arg("--metric", 1).map_or(Metric::Manhattan, |m| {
+        Metric::parse(m).expect("metric should be manhattan or chebyshev")
+    });
+
+    if let (Some(galaxy), Some(k)) = (arg("--nearest", 1), arg("--nearest", 2)) {
+        let galaxy = galaxy.parse::<usize>().expect("galaxy should be a number");
+        if galaxy == 0 || galaxy > grid.galaxies.len() {
+            panic!("galaxy should be between 1 and {}", grid.galaxies.len());
+        }
+        let k = k.parse().expect("k should be a number");
+        println!("Nearest to galaxy {}:", galaxy);
+        for (other, d) in grid.nearest(galaxy - 1, k, factor, metric) {
+            println!("  galaxy {} at {}", other + 1, d);
         }
     }
 
-    println!("Part 1: {:?}", part1_distances.iter().sum::<usize>());
-    println!("Part 2: {:?}", part2_distances.iter().sum::<usize>());
+    if args.iter().any(|a| a == "--farthest") {
+        match grid.farthest_pair(factor, metric) {
+            Some((a, b, d)) => println!("Farthest pair: galaxies {} and {} at {}", a + 1, b + 1, d),
+            None => println!("Farthest pair: fewer than two galaxies"),
+        }
+    }
+
+    if let Some(path) = arg("--matrix", 1) {
+        std::fs::write(path, grid.distance_matrix_csv(factor, metric))
+            .expect("should be able to write the distance matrix");
+        println!("Wrote distance matrix to {}", path);
+    }
+}
+
+fn part1(grid: &SpaceGrid) {
+    println!("Part 1: {}", grid.total_distance(2));
 }
 
-fn _part2(_input: &str) {
-    todo!()
+fn part2(grid: &SpaceGrid) {
+    println!("Part 2: {}", grid.total_distance(1_000_000));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_distance() {
+        let grid = SpaceGrid::new(EXAMPLE_INPUT);
+        assert_eq!(grid.total_distance(1), 292);
+        assert_eq!(grid.total_distance(2), 374);
+        assert_eq!(grid.total_distance(10), 1030);
+        assert_eq!(grid.total_distance(100), 8410);
+    }
+
+    #[test]
+    fn test_queries() {
+        let grid = SpaceGrid::new(EXAMPLE_INPUT);
+        // galaxies 5 and 9 from the puzzle text are 9 apart
+        let nearest = grid.nearest(4, 8, 2, Metric::Manhattan);
+        assert_eq!(nearest[0], (2, 5));
+        assert!(nearest.contains(&(8, 9)));
+        assert_eq!(
+            grid.distance_matrix_csv(2, Metric::Manhattan)
+                .lines()
+                .nth(5)
+                .unwrap()
+                .split(',')
+                .nth(9),
+            Some("9")
+        );
+
+        // brute force every pair to check the rotated-axes shortcut
+        for metric in [Metric::Manhattan, Metric::Chebyshev] {
+            for factor in [1, 2, 10] {
+                let galaxies = grid.expanded_galaxies(factor);
+                let max = (0..galaxies.len())
+                    .flat_map(|a| (0..galaxies.len()).map(move |b| (a, b)))
+                    .map(|(a, b)| metric.distance(galaxies[a], galaxies[b]))
+                    .max();
+                assert_eq!(grid.farthest_pair(factor, metric).map(|(_, _, d)| d), max);
+            }
+        }
+        assert_eq!(
+            SpaceGrid::new("#..").farthest_pair(2, Metric::Manhattan),
+            None
+        );
+    }
 }
@@ -1,5 +1,6 @@
-use std::{collections::HashMap, iter::repeat};
+use std::{collections::HashMap, iter::repeat_n};
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 
 static EXAMPLE_INPUT: &str = r#"???.### 1,1,3
@@ -14,9 +15,16 @@ static EXAMPLE_INPUT_2: &str = r#"????????#??? 2,3"#;
 fn main() {
     println!("\n-- Advent of Code 2023 - Day 12 --");
 
-    // let input = EXAMPLE_INPUT;
-    // let input = EXAMPLE_INPUT_2;
-    let input = include_str!("input.txt");
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg = |flag: &str, n: usize| args.iter().skip_while(|a| *a != flag).nth(n);
+
+    let input = if args.iter().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else if args.iter().any(|a| a == "--example2") {
+        EXAMPLE_INPUT_2
+    } else {
+        include_str!("input.txt")
+    };
 
     let start = std::time::Instant::now();
     part1(input);
@@ -24,23 +32,62 @@ fn main() {
     let start = std::time::Instant::now();
     part2(input);
     println!("Time: {:?}\n", start.elapsed());
+
+    let row = |n: &String, copies: usize| {
+        let n = n.parse::<usize>().expect("row should be a number");
+        let line = input
+            .lines()
+            .nth(n.wrapping_sub(1))
+            .unwrap_or_else(|| panic!("row should be between 1 and {}", input.lines().count()));
+        parse_row(line, copies)
+    };
+
+    if let Some(n) = arg("--arrangements", 1) {
+        let (pattern, numbers) = row(n, 1);
+        for arrangement in Arrangements::new(&pattern, &numbers) {
+            println!("{}", String::from_utf8(arrangement).unwrap());
+        }
+    }
+
+    if let (Some(n), Some(samples)) = (arg("--sample", 1), arg("--sample", 2)) {
+        let (pattern, numbers) = row(n, 5);
+        let samples = samples.parse().expect("samples should be a number");
+        let mut rng = match arg("--seed", 1) {
+            Some(seed) => StdRng::seed_from_u64(seed.parse().expect("seed should be a number")),
+            None => StdRng::from_entropy(),
+        };
+        let mut cache = HashMap::new();
+        for _ in 0..samples {
+            match sample_arrangement(&pattern, &numbers, &mut cache, &mut rng) {
+                Some(arrangement) => println!("{}", String::from_utf8(arrangement).unwrap()),
+                None => println!("No valid arrangements"),
+            }
+        }
+    }
+}
+
+/// Parses a row of springs and its damaged group sizes, unfolded into
+/// `copies` copies joined by `?`.
+fn parse_row(line: &str, copies: usize) -> (Vec<u8>, Vec<usize>) {
+    let mut parts = line.split_whitespace();
+
+    let pattern = repeat_n(parts.next().unwrap(), copies)
+        .collect::<Vec<_>>()
+        .join("?")
+        .into_bytes();
+
+    let numbers = repeat_n(parts.next().unwrap(), copies)
+        .collect::<Vec<_>>()
+        .join(",")
+        .split(',')
+        .map(|n| n.parse::<usize>().unwrap())
+        .collect::<Vec<_>>();
+
+    (pattern, numbers)
 }
 
 fn part1(input: &str) {
-    let lines: Vec<(Vec<u8>, Vec<usize>)> = input
-        .lines()
-        .map(|line| {
-            let mut parts = line.split_whitespace();
-            let pattern = parts.next().unwrap().chars().map(|c| c as u8).collect();
-            let numbers = parts
-                .next()
-                .unwrap()
-                .split(',')
-                .map(|n| n.parse::<usize>().unwrap())
-                .collect();
-            (pattern, numbers)
-        })
-        .collect();
+    let lines: Vec<(Vec<u8>, Vec<usize>)> = input.lines().map(|line| parse_row(line, 1)).collect();
 
     let arrangements_sum = (0..lines.len())
         .into_par_iter()
@@ -49,36 +96,13 @@ fn part1(input: &str) {
             let (pattern, numbers) = &lines[i];
             count_arrangements(pattern, numbers, 0, 0, 0, &mut HashMap::new())
         })
-        .sum::<usize>();
+        .sum::<u128>();
 
     println!("Part 1: {:?}", arrangements_sum);
 }
 
-fn part2(_input: &str) {
-    let lines: Vec<(Vec<u8>, Vec<usize>)> = _input
-        .lines()
-        .map(|line| {
-            let mut parts = line.split_whitespace();
-
-            let repeated_pattern = repeat(parts.next().unwrap())
-                .take(5)
-                .collect::<Vec<_>>()
-                .join("?")
-                .chars()
-                .map(|c| c as u8)
-                .collect::<Vec<_>>();
-
-            let repeated_numbers = repeat(parts.next().unwrap())
-                .take(5)
-                .collect::<Vec<_>>()
-                .join(",")
-                .split(',')
-                .map(|n| n.parse::<usize>().unwrap())
-                .collect::<Vec<_>>();
-
-            (repeated_pattern, repeated_numbers)
-        })
-        .collect();
+fn part2(input: &str) {
+    let lines: Vec<(Vec<u8>, Vec<usize>)> = input.lines().map(|line| parse_row(line, 5)).collect();
 
     let sum = (0..lines.len())
         .into_par_iter()
@@ -87,7 +111,7 @@ fn part2(_input: &str) {
             let (pattern, numbers) = &lines[i];
             count_arrangements(pattern, numbers, 0, 0, 0, &mut HashMap::new())
         })
-        .sum::<usize>();
+        .sum::<u128>();
 
     println!("Part 2: {:?}", sum);
 }
@@ -105,8 +129,8 @@ fn count_arrangements(
     pattern_index: usize,
     number_index: usize,
     damaged_count: usize,
-    cache: &mut HashMap<MemoKey, usize>,
-) -> usize {
+    cache: &mut HashMap<MemoKey, u128>,
+) -> u128 {
     if let Some(&arrangements) = cache.get(&MemoKey {
         pattern_index,
         number_index,
@@ -115,27 +139,74 @@ fn count_arrangements(
         return arrangements;
     }
 
-    let mut pattern_index = pattern_index;
-    let mut number_index = number_index;
-    let mut damaged_count = damaged_count;
+    let Some((pattern_index, number_index, damaged_count)) =
+        advance(pattern, numbers, pattern_index, number_index, damaged_count)
+    else {
+        return 0;
+    };
+    if pattern_index == pattern.len() {
+        return 1;
+    }
+
+    // reached a '?' in the pattern
+    let arrangements = choices(numbers, number_index, damaged_count)
+        .into_iter()
+        .map(|(_, number_index, damaged_count)| {
+            count_arrangements(
+                pattern,
+                numbers,
+                pattern_index + 1,
+                number_index,
+                damaged_count,
+                cache,
+            )
+        })
+        .fold(0u128, |total, n| {
+            // unfolded rows can have more arrangements than fit in a u64,
+            // so fail loudly rather than wrap if even u128 runs out
+            total
+                .checked_add(n)
+                .expect("arrangement count should fit in a u128")
+        });
+
+    cache.insert(
+        MemoKey {
+            pattern_index,
+            number_index,
+            damaged_count,
+        },
+        arrangements,
+    );
+    arrangements
+}
 
+/// Walks over the known springs from the given state up to the next `?`,
+/// returning the state there, or at the end of the pattern if the row is
+/// complete and valid. `None` means no arrangement can follow.
+fn advance(
+    pattern: &[u8],
+    numbers: &[usize],
+    mut pattern_index: usize,
+    mut number_index: usize,
+    mut damaged_count: usize,
+) -> Option<(usize, usize, usize)> {
     loop {
         // if end of pattern reached
         if pattern_index == pattern.len() {
             let valid_end = (number_index == numbers.len() && damaged_count == 0) // gone past but no damage
                 || (number_index == numbers.len() - 1 && damaged_count == numbers[number_index]); // at last number and correct damage
 
-            return if valid_end { 1 } else { 0 };
+            return valid_end.then_some((pattern_index, number_index, damaged_count));
         }
 
         match pattern[pattern_index] {
-            b'?' => break,
+            b'?' => return Some((pattern_index, number_index, damaged_count)),
             b'#' => {
                 let invalid_damage =
                     number_index > numbers.len() - 1 || damaged_count > numbers[number_index];
 
                 if invalid_damage {
-                    return 0;
+                    return None;
                 }
 
                 damaged_count += 1;
@@ -152,62 +223,248 @@ fn count_arrangements(
                     damaged_count = 0;
                 } else {
                     // invalid end of group
-                    return 0;
+                    return None;
                 }
             }
             _ => unreachable!(),
         }
     }
+}
 
-    // reached a '?' in the pattern
-    let mut arrangements = 0;
-
+/// What a `?` can be replaced with given the current group, and the
+/// `(number_index, damaged_count)` state after it.
+fn choices(
+    numbers: &[usize],
+    number_index: usize,
+    damaged_count: usize,
+) -> Vec<(u8, usize, usize)> {
     if damaged_count == 0 {
         if number_index < numbers.len() {
-            arrangements +=
-                count_arrangements(pattern, numbers, pattern_index + 1, number_index, 1, cache)
-                    + count_arrangements(
-                        pattern,
-                        numbers,
-                        pattern_index + 1,
-                        number_index,
-                        0,
-                        cache,
-                    );
+            vec![(b'#', number_index, 1), (b'.', number_index, 0)]
         } else {
             // no more numbers, only valid if no damage
-            arrangements +=
-                count_arrangements(pattern, numbers, pattern_index + 1, number_index, 0, cache);
+            vec![(b'.', number_index, 0)]
         }
     } else if damaged_count == numbers[number_index] {
         // valid amount of damage, go to next number
-        arrangements += count_arrangements(
-            pattern,
-            numbers,
-            pattern_index + 1,
-            number_index + 1,
-            0,
-            cache,
-        );
+        vec![(b'.', number_index + 1, 0)]
     } else {
         // '?' needs to be damaged
-        arrangements += count_arrangements(
+        vec![(b'#', number_index, damaged_count + 1)]
+    }
+}
+
+/// Lazily yields every concrete arrangement of a row, with each `?`
+/// replaced by `#` or `.`.
+///
+/// This is a depth first search over the `?`s, but branches with no
+/// completions (according to `count_arrangements`) are never entered, so
+/// every step towards the next arrangement makes progress.
+struct Arrangements<'a> {
+    pattern: &'a [u8],
+    numbers: &'a [usize],
+    cache: HashMap<MemoKey, u128>,
+    stack: Vec<(Vec<u8>, usize, usize, usize)>,
+}
+
+impl<'a> Arrangements<'a> {
+    fn new(pattern: &'a [u8], numbers: &'a [usize]) -> Self {
+        let stack = advance(pattern, numbers, 0, 0, 0)
+            .map(|(pattern_index, number_index, damaged_count)| {
+                (pattern.to_vec(), pattern_index, number_index, damaged_count)
+            })
+            .into_iter()
+            .collect();
+
+        Self {
+            pattern,
+            numbers,
+            cache: HashMap::new(),
+            stack,
+        }
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((row, pattern_index, number_index, damaged_count)) = self.stack.pop() {
+            if pattern_index == self.pattern.len() {
+                return Some(row);
+            }
+
+            // pushed in reverse so `#` is tried before `.`
+            for (spring, number_index, damaged_count) in
+                choices(self.numbers, number_index, damaged_count)
+                    .into_iter()
+                    .rev()
+            {
+                let completions = count_arrangements(
+                    self.pattern,
+                    self.numbers,
+                    pattern_index + 1,
+                    number_index,
+                    damaged_count,
+                    &mut self.cache,
+                );
+                if completions == 0 {
+                    continue;
+                }
+
+                let mut row = row.clone();
+                row[pattern_index] = spring;
+                let (pattern_index, number_index, damaged_count) = advance(
+                    self.pattern,
+                    self.numbers,
+                    pattern_index + 1,
+                    number_index,
+                    damaged_count,
+                )
+                .expect("a branch with completions should advance");
+                self.stack
+                    .push((row, pattern_index, number_index, damaged_count));
+            }
+        }
+        None
+    }
+}
+
+/// Picks one arrangement of a row uniformly at random, or `None` if there
+/// are none.
+///
+/// Each `?` is decided in turn, choosing between `#` and `.` in proportion
+/// to how many arrangements each leaves, so every complete arrangement is
+/// equally likely. `cache` can be reused between samples of the same row.
+fn sample_arrangement(
+    pattern: &[u8],
+    numbers: &[usize],
+    cache: &mut HashMap<MemoKey, u128>,
+    rng: &mut impl Rng,
+) -> Option<Vec<u8>> {
+    let mut row = pattern.to_vec();
+    let (mut pattern_index, mut number_index, mut damaged_count) =
+        advance(pattern, numbers, 0, 0, 0)?;
+
+    while pattern_index < pattern.len() {
+        let weighted = choices(numbers, number_index, damaged_count)
+            .into_iter()
+            .map(|choice| {
+                let completions = count_arrangements(
+                    pattern,
+                    numbers,
+                    pattern_index + 1,
+                    choice.1,
+                    choice.2,
+                    cache,
+                );
+                (choice, completions)
+            })
+            .collect::<Vec<_>>();
+
+        let total = weighted
+            .iter()
+            .map(|(_, completions)| completions)
+            .sum::<u128>();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        let &((spring, next_number, next_damaged), _) = weighted
+            .iter()
+            .find(|(_, completions)| {
+                let found = pick < *completions;
+                if !found {
+                    pick -= completions;
+                }
+                found
+            })
+            .unwrap();
+
+        row[pattern_index] = spring;
+        (pattern_index, number_index, damaged_count) = advance(
             pattern,
             numbers,
             pattern_index + 1,
-            number_index,
-            damaged_count + 1,
-            cache,
+            next_number,
+            next_damaged,
+        )
+        .expect("a branch with completions should advance");
+    }
+
+    Some(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrangements() {
+        let expected = [1, 4, 1, 1, 4, 10];
+        for (line, expected) in EXAMPLE_INPUT.lines().zip(expected) {
+            let (pattern, numbers) = parse_row(line, 1);
+            let arrangements = Arrangements::new(&pattern, &numbers).collect::<Vec<_>>();
+            assert_eq!(arrangements.len(), expected);
+
+            // every arrangement is distinct, fills in exactly the `?`s and
+            // has the right groups of damaged springs
+            let distinct = arrangements
+                .iter()
+                .collect::<std::collections::HashSet<_>>();
+            assert_eq!(distinct.len(), expected);
+            for arrangement in &arrangements {
+                assert!(pattern
+                    .iter()
+                    .zip(arrangement)
+                    .all(|(&p, &a)| p == a || (p == b'?' && a != b'?')));
+                let groups = arrangement
+                    .split(|&c| c == b'.')
+                    .filter(|g| !g.is_empty())
+                    .map(|g| g.len())
+                    .collect::<Vec<_>>();
+                assert_eq!(groups, numbers);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_arrangement() {
+        let (pattern, numbers) = parse_row("?###???????? 3,2,1", 1);
+        let all = Arrangements::new(&pattern, &numbers).collect::<Vec<_>>();
+
+        let mut rng = StdRng::seed_from_u64(12);
+        let mut cache = HashMap::new();
+        let mut counts = HashMap::new();
+        for _ in 0..10_000 {
+            let sample = sample_arrangement(&pattern, &numbers, &mut cache, &mut rng).unwrap();
+            *counts.entry(sample).or_insert(0) += 1;
+        }
+        // 10 arrangements, so each should come up about 1000 times
+        assert_eq!(counts.len(), all.len());
+        assert!(counts.values().all(|&n| (850..1150).contains(&n)));
+
+        let (pattern, numbers) = parse_row("#.# 3", 1);
+        assert_eq!(
+            sample_arrangement(&pattern, &numbers, &mut HashMap::new(), &mut rng),
+            None
         );
-    };
+    }
 
-    cache.insert(
-        MemoKey {
-            pattern_index,
-            number_index,
-            damaged_count,
-        },
-        arrangements,
-    );
-    arrangements
+    #[test]
+    fn test_sample_beyond_u64() {
+        // 25 single damaged springs in 104 unknowns: C(80, 25) arrangements
+        let (pattern, numbers) = parse_row("???????????????????? 1,1,1,1,1", 5);
+        let expected = (0..25u128).fold(1, |c, k| c * (80 - k) / (k + 1));
+        let mut cache = HashMap::new();
+        let count = count_arrangements(&pattern, &numbers, 0, 0, 0, &mut cache);
+        assert_eq!(count, expected);
+        assert!(count > u64::MAX as u128);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let sample = sample_arrangement(&pattern, &numbers, &mut cache, &mut rng).unwrap();
+        assert!(!sample.contains(&b'?'));
+        assert!(!sample.windows(2).any(|w| w == b"##"));
+        assert_eq!(sample.iter().filter(|&&c| c == b'#').count(), 25);
+    }
 }
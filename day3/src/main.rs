@@ -1,4 +1,16 @@
 use itertools::Itertools;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{digit1, none_of},
+    combinator::{map, map_res, value},
+    multi::separated_list1,
+    sequence::{separated_pair, tuple},
+    IResult,
+};
+use render::{Renderer, Viewport};
+
+mod render;
 
 static EXAMPLE_INPUT: &str = r#"467..114..
 ...*......
@@ -42,143 +54,455 @@ impl Grid {
         }
     }
 
-    fn get(&self, x: usize, y: usize) -> Option<char> {
-        self.data.get(y)?.get(x).copied()
+    /// Cells touching `(x, y)`, including diagonals, clamped to the grid.
+    fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (y.saturating_sub(1)..=(y + 1).min(self.height - 1))
+            .cartesian_product(x.saturating_sub(1)..=(x + 1).min(self.width - 1))
+            .map(|(y, x)| (x, y))
+            .filter(move |&(nx, ny)| nx != x || ny != y)
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NumberSpan {
+    value: usize,
+    y: usize,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GridSymbol {
+    symbol: char,
+    x: usize,
+    y: usize,
+}
+
+/// Parsed schematic with every number and symbol located once up front.
+///
+/// Each cell covered by a number points back to its index in `numbers`, and
+/// the adjacency lists between numbers and symbols are built in a single pass,
+/// so all queries below are lookups rather than grid rescans.
+#[derive(Debug)]
+struct Schematic {
+    grid: Grid,
+    numbers: Vec<NumberSpan>,
+    symbols: Vec<GridSymbol>,
+    /// `cell_numbers[y * width + x]` is the index of the number covering that cell.
+    cell_numbers: Vec<Option<usize>>,
+    /// Indices into `numbers` adjacent to each symbol, sorted and deduplicated.
+    symbol_numbers: Vec<Vec<usize>>,
+    /// Indices into `symbols` adjacent to each number, sorted and deduplicated.
+    number_symbols: Vec<Vec<usize>>,
+}
+
+impl Schematic {
+    fn new(input: &str) -> Self {
+        let grid = Grid::new(input);
+        let mut numbers = Vec::new();
+        let mut symbols = Vec::new();
+        let mut cell_numbers = vec![None; grid.width * grid.height];
 
-    fn get_number(&self, x: usize, y: usize) -> Option<(String, usize)> {
-        let c = self.get(x, y)?;
+        for (y, line) in grid.data.iter().enumerate() {
+            let mut x = 0;
+            while x < line.len() {
+                let c = line[x];
+                if c.is_ascii_digit() {
+                    let start = x;
+                    let mut value = 0;
+                    while let Some(d) = line.get(x).and_then(|c| c.to_digit(10)) {
+                        value = value * 10 + d as usize;
+                        cell_numbers[y * grid.width + x] = Some(numbers.len());
+                        x += 1;
+                    }
+                    numbers.push(NumberSpan {
+                        value,
+                        y,
+                        start,
+                        end: x - 1,
+                    });
+                    continue;
+                }
 
-        if c.is_ascii_digit() {
-            let mut start_idx = x;
-            let mut end_idx = x;
-            // walk left
-            while let Some(c) = self.get(start_idx.saturating_sub(1), y) {
-                if c.is_ascii_digit() && start_idx > 0 {
-                    start_idx -= 1;
-                } else {
-                    break;
+                if c != '.' {
+                    symbols.push(GridSymbol { symbol: c, x, y });
                 }
+                x += 1;
             }
-            // walk right
-            while let Some(c) = self.get(end_idx + 1, y) {
-                if c.is_ascii_digit() {
-                    end_idx += 1;
-                } else {
-                    break;
+        }
+
+        let mut schematic = Self {
+            grid,
+            numbers,
+            symbols,
+            cell_numbers,
+            symbol_numbers: Vec::new(),
+            number_symbols: Vec::new(),
+        };
+        schematic.link_adjacent();
+
+        schematic
+    }
+
+    fn link_adjacent(&mut self) {
+        let mut symbol_numbers = vec![Vec::new(); self.symbols.len()];
+        let mut number_symbols = vec![Vec::new(); self.numbers.len()];
+        for (symbol_idx, symbol) in self.symbols.iter().enumerate() {
+            let adjacent = &mut symbol_numbers[symbol_idx];
+            for (x, y) in self.grid.neighbours(symbol.x, symbol.y) {
+                if let Some(number_idx) = self.number_idx_at(x, y) {
+                    adjacent.push(number_idx);
                 }
             }
+            adjacent.sort_unstable();
+            adjacent.dedup();
 
-            let mut number = String::new();
-            for x in start_idx..=end_idx {
-                number.push(self.get(x, y)?);
+            for &number_idx in adjacent.iter() {
+                number_symbols[number_idx].push(symbol_idx);
             }
+        }
+
+        self.symbol_numbers = symbol_numbers;
+        self.number_symbols = number_symbols;
+    }
 
-            Some((
-                format!("{}-{}-{}", y, start_idx, end_idx),
-                number.parse().unwrap(),
-            ))
-        } else {
-            None
+    fn number_idx_at(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.grid.width || y >= self.grid.height {
+            return None;
         }
+
+        self.cell_numbers[y * self.grid.width + x]
     }
 
-    fn get_symbols(&self) -> Vec<GridSymbol> {
-        let mut symbols = Vec::new();
+    fn adjacent_numbers(&self, symbol_idx: usize) -> impl Iterator<Item = &NumberSpan> {
+        self.symbol_numbers[symbol_idx]
+            .iter()
+            .map(|&idx| &self.numbers[idx])
+    }
 
-        for (y, line) in self.data.iter().enumerate() {
-            for (x, c) in line.iter().enumerate() {
-                if !c.is_ascii_digit() && *c != '.' {
-                    symbols.push(GridSymbol::new(*c, x, y));
-                }
-            }
+    fn is_part_number(&self, number_idx: usize) -> bool {
+        !self.number_symbols[number_idx].is_empty()
+    }
+
+    fn part_numbers(&self) -> impl Iterator<Item = &NumberSpan> {
+        self.numbers
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.is_part_number(*idx))
+            .map(|(_, n)| n)
+    }
+
+    fn isolated_numbers(&self) -> impl Iterator<Item = &NumberSpan> {
+        self.numbers
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !self.is_part_number(*idx))
+            .map(|(_, n)| n)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NeighbourCount {
+    Exact(usize),
+    Min(usize),
+    Max(usize),
+}
+
+impl NeighbourCount {
+    fn matches(&self, count: usize) -> bool {
+        match *self {
+            NeighbourCount::Exact(n) => count == n,
+            NeighbourCount::Min(n) => count >= n,
+            NeighbourCount::Max(n) => count <= n,
         }
+    }
 
-        symbols
+    fn parse(i: &str) -> IResult<&str, Self> {
+        alt((
+            map(tuple((tag(">="), parse_number)), |(_, n)| Self::Min(n)),
+            map(tuple((tag("<="), parse_number)), |(_, n)| Self::Max(n)),
+            map(tuple((tag("="), parse_number)), |(_, n)| Self::Exact(n)),
+        ))(i)
     }
 }
 
-#[derive(Debug)]
-struct GridSymbol {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Aggregation {
+    Product,
+    Sum,
+    Max,
+}
+
+impl Aggregation {
+    /// Combines the values, or `None` if the result overflows a `usize`.
+    fn apply(&self, mut values: impl Iterator<Item = usize>) -> Option<usize> {
+        match self {
+            Aggregation::Product => values.try_fold(1_usize, |acc, v| acc.checked_mul(v)),
+            Aggregation::Sum => values.try_fold(0_usize, |acc, v| acc.checked_add(v)),
+            Aggregation::Max => Some(values.max().unwrap_or(0)),
+        }
+    }
+
+    fn parse(i: &str) -> IResult<&str, Self> {
+        alt((
+            value(Self::Product, tag("product")),
+            value(Self::Sum, tag("sum")),
+            value(Self::Max, tag("max")),
+        ))(i)
+    }
+}
+
+/// Which symbol counts as a gear, how many numbers it must touch, and how
+/// those numbers combine into its ratio.
+///
+/// Written as `<symbol><op><count>:<aggregation>`, e.g. `*=2:product` for the
+/// original puzzle or `#>=1:sum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GearRule {
     symbol: char,
-    x: usize,
-    y: usize,
+    count: NeighbourCount,
+    aggregation: Aggregation,
 }
 
-impl GridSymbol {
-    fn new(symbol: char, x: usize, y: usize) -> Self {
-        Self { symbol, x, y }
+impl GearRule {
+    fn parse(i: &str) -> IResult<&str, Self> {
+        map(
+            separated_pair(
+                tuple((none_of("0123456789.,:"), NeighbourCount::parse)),
+                tag(":"),
+                Aggregation::parse,
+            ),
+            |((symbol, count), aggregation)| Self {
+                symbol,
+                count,
+                aggregation,
+            },
+        )(i)
     }
 
-    fn surrounding_numbers(&self, grid: &Grid) -> Vec<usize> {
-        let mut numbers = Vec::new();
+    /// Whether the symbol at `symbol_idx` is a gear under this rule.
+    fn applies(&self, schematic: &Schematic, symbol_idx: usize) -> bool {
+        let count = schematic.symbol_numbers[symbol_idx].len();
+        schematic.symbols[symbol_idx].symbol == self.symbol
+            && count > 0
+            && self.count.matches(count)
+    }
 
-        for y in self.y.saturating_sub(1)..=(self.y + 1).min(grid.height - 1) {
-            for x in self.x.saturating_sub(1)..=(self.x + 1).min(grid.width - 1) {
-                if x == self.x && y == self.y {
-                    continue;
-                }
+    /// Ratio of the symbol at `symbol_idx`, or `None` if it overflows.
+    fn ratio(&self, schematic: &Schematic, symbol_idx: usize) -> Option<usize> {
+        self.aggregation
+            .apply(schematic.adjacent_numbers(symbol_idx).map(|n| n.value))
+    }
+}
 
-                if let Some(c) = grid.get_number(x, y) {
-                    numbers.push(c);
-                }
-            }
+/// Comma separated list of [`GearRule`]s. When several rules share a symbol,
+/// they're tried in order and the first whose count matches wins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RuleSet {
+    rules: Vec<GearRule>,
+}
+
+impl RuleSet {
+    fn parse(i: &str) -> IResult<&str, Self> {
+        map(separated_list1(tag(","), GearRule::parse), |rules| Self {
+            rules,
+        })(i)
+    }
+
+    /// The original puzzle: `*` touching exactly two numbers, multiplied.
+    fn gears() -> Self {
+        Self {
+            rules: vec![GearRule {
+                symbol: '*',
+                count: NeighbourCount::Exact(2),
+                aggregation: Aggregation::Product,
+            }],
         }
+    }
 
-        // remove numbers with the same id
-        numbers.sort_by(|a, b| a.0.cmp(&b.0));
-        numbers.dedup_by(|a, b| a.0 == b.0);
-        numbers
-            .iter()
-            .map(|(_, n)| *n)
-            .collect::<Vec<usize>>()
-            .into_iter()
-            .sorted()
-            .collect()
+    /// The rule that makes the symbol at `symbol_idx` a gear, if any.
+    fn rule_for(&self, schematic: &Schematic, symbol_idx: usize) -> Option<&GearRule> {
+        self.rules.iter().find(|r| r.applies(schematic, symbol_idx))
+    }
+
+    /// Sum of every gear's ratio, or `None` if it overflows.
+    fn evaluate(&self, schematic: &Schematic) -> Option<usize> {
+        (0..schematic.symbols.len())
+            .filter_map(|idx| Some(self.rule_for(schematic, idx)?.ratio(schematic, idx)))
+            .try_fold(0_usize, |acc, ratio| acc.checked_add(ratio?))
     }
 }
 
 fn main() {
     println!("-- Advent of Code 2023 - Day 3 --");
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
 
     // time execution
     let start = std::time::Instant::now();
-    part1(input);
-    part2(input);
+    let schematic = Schematic::new(input);
+    part1(&schematic);
+    part2(&schematic);
+    if let Some(spec) = std::env::args().skip_while(|a| a != "--rules").nth(1) {
+        custom_rules(&schematic, &spec);
+    }
     let duration = start.elapsed();
 
+    if std::env::args().any(|a| a == "--render") {
+        render(&schematic);
+    }
+
     println!("Finished after {} ms", duration.as_millis(),);
 }
 
-fn part1(input: &str) {
-    let grid = Grid::new(input);
+fn part1(schematic: &Schematic) {
+    let sum = schematic.part_numbers().map(|n| n.value).sum::<usize>();
+    println!("Part 1: {}", sum);
+
+    let isolated = schematic.isolated_numbers().count();
+    println!("Numbers adjacent to no symbol: {}", isolated);
+}
 
-    let symbols = grid.get_symbols();
+fn part2(schematic: &Schematic) {
+    match RuleSet::gears().evaluate(schematic) {
+        Some(sum) => println!("Part 2: {}", sum),
+        None => println!("Part 2: gear ratios overflow"),
+    }
+}
 
-    let all_surrounding_numbers: Vec<usize> = symbols
-        .iter()
-        .flat_map(|s| s.surrounding_numbers(&grid))
-        .collect();
+fn custom_rules(schematic: &Schematic, spec: &str) {
+    let (rest, rules) = RuleSet::parse(spec).expect("invalid rule set");
+    assert!(rest.is_empty(), "unexpected trailing rule input: {}", rest);
 
-    let sum = all_surrounding_numbers.iter().sum::<usize>();
-    println!("Part 1: {}", sum);
+    match rules.evaluate(schematic) {
+        Some(sum) => println!("Rules {}: {}", spec, sum),
+        None => println!("Rules {}: gear ratios overflow", spec),
+    }
+}
+
+fn render(schematic: &Schematic) {
+    let rules = match std::env::args().skip_while(|a| a != "--rules").nth(1) {
+        Some(spec) => RuleSet::parse(&spec).expect("invalid rule set").1,
+        None => RuleSet::gears(),
+    };
+
+    let mut renderer = Renderer::new(schematic, &rules);
+    if let Some(spec) = std::env::args().skip_while(|a| a != "--viewport").nth(1) {
+        let viewport = Viewport::parse(&spec).expect("viewport should be x,y,width,height");
+        renderer = renderer.with_viewport(viewport);
+    }
+
+    print!("{}", renderer);
 }
 
-fn part2(input: &str) {
-    let grid = Grid::new(input);
+fn parse_number(i: &str) -> IResult<&str, usize> {
+    map_res(digit1, |s: &str| s.parse::<usize>())(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_sets() {
+        let schematic = Schematic::new(EXAMPLE_INPUT);
+        let evaluate = |spec| {
+            RuleSet::parse(spec)
+                .unwrap()
+                .1
+                .evaluate(&schematic)
+                .unwrap()
+        };
+
+        assert_eq!(evaluate("*=2:product"), 467835);
+        assert_eq!(evaluate("*>=1:max"), 467 + 617 + 755);
+        assert_eq!(evaluate("*<=1:sum,#=1:sum"), 617 + 633);
+        // both `*` rules fire, each on the symbols whose count it matches
+        assert_eq!(evaluate("*=1:sum,*=2:product"), 617 + 467835);
+        assert_eq!(evaluate("*>=1:sum,*=2:product"), 467 + 35 + 617 + 755 + 598);
+
+        // two numbers this big multiply past a usize
+        let schematic = Schematic::new(
+            "18446744073709551615.\n\
+             ....................*\n\
+             18446744073709551615.",
+        );
+        let rules = RuleSet::parse("*>=2:product").unwrap().1;
+        assert_eq!(rules.evaluate(&schematic), None);
+        let rules = RuleSet::parse("*>=2:max").unwrap().1;
+        assert_eq!(rules.evaluate(&schematic), Some(usize::MAX));
+    }
 
-    let symbols = grid.get_symbols();
-    let star_symbols_with_two_surrounding_numbers = symbols
-        .iter()
-        .filter(|s| s.symbol == '*')
-        .filter(|s| s.surrounding_numbers(&grid).len() == 2);
+    #[test]
+    fn test_part_numbers() {
+        let schematic = Schematic::new(EXAMPLE_INPUT);
+        assert_eq!(
+            schematic.part_numbers().map(|n| n.value).sum::<usize>(),
+            4361
+        );
+        assert_eq!(
+            schematic
+                .isolated_numbers()
+                .map(|n| n.value)
+                .collect::<Vec<_>>(),
+            [114, 58]
+        );
+
+        // 12 touches both symbols but is still only one part number
+        let schematic = Schematic::new("*12\n..#");
+        assert_eq!(schematic.number_symbols[0].len(), 2);
+        assert_eq!(schematic.part_numbers().map(|n| n.value).sum::<usize>(), 12);
+    }
 
-    let sum_of_products = star_symbols_with_two_surrounding_numbers
-        .map(|s| s.surrounding_numbers(&grid).iter().product::<usize>())
-        .sum::<usize>();
+    #[test]
+    fn test_viewport() {
+        assert_eq!(
+            Viewport::parse("1, 2,3,4"),
+            Some(Viewport {
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 4
+            })
+        );
+        assert_eq!(Viewport::parse("1,2,3"), None);
+        assert_eq!(Viewport::parse("1,2,3,4,5"), None);
+        assert_eq!(Viewport::parse("1,2,-3,4"), None);
+    }
 
-    println!("Part 2: {}", sum_of_products);
+    #[test]
+    fn test_render_colors() {
+        let schematic = Schematic::new(EXAMPLE_INPUT);
+        let render = |spec: &str| {
+            Renderer::new(&schematic, &RuleSet::gears())
+                .with_viewport(Viewport::parse(spec).unwrap())
+                .to_string()
+        };
+        let cell = |color: &str, c: char| format!("{}{}\x1b[0m ", color, c);
+
+        // 35 is a gear number, then a dot and 633 which only touches `#`
+        let expected = [
+            ("\x1b[33m", '3'),
+            ("\x1b[33m", '5'),
+            ("\x1b[90m", '.'),
+            ("\x1b[90m", '.'),
+            ("\x1b[32m", '6'),
+        ]
+        .map(|(color, c)| cell(color, c))
+        .concat();
+        assert_eq!(render("2,2,5,1"), expected + "\n");
+
+        // 114 touches no symbol, and a viewport this wide doesn't overflow
+        let row = render(&format!("5,0,{},1", usize::MAX));
+        assert!(row.starts_with(&cell("\x1b[31m", '1')));
+        assert_eq!(row.matches('\x1b').count(), 10);
+
+        // the gear between 467 and 35, and a `*` with only one number
+        assert_eq!(render("3,1,1,1"), cell("\x1b[1;33m", '*') + "\n");
+        assert_eq!(render("3,4,1,1"), cell("\x1b[1;36m", '*') + "\n");
+    }
 }
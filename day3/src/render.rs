@@ -0,0 +1,123 @@
+use std::fmt;
+
+use crate::{RuleSet, Schematic};
+
+const RESET: &str = "\x1b[0m";
+const PART_NUMBER: &str = "\x1b[32m";
+const LOOSE_NUMBER: &str = "\x1b[31m";
+const GEAR: &str = "\x1b[1;33m";
+const GEAR_NUMBER: &str = "\x1b[33m";
+const SYMBOL: &str = "\x1b[1;36m";
+const EMPTY: &str = "\x1b[90m";
+
+/// Rectangle of the schematic to draw, clamped to the grid when rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    /// Parses `x,y,width,height`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split(',').map(|p| p.trim().parse::<usize>());
+        let viewport = Self {
+            x: parts.next()?.ok()?,
+            y: parts.next()?.ok()?,
+            width: parts.next()?.ok()?,
+            height: parts.next()?.ok()?,
+        };
+
+        parts.next().is_none().then_some(viewport)
+    }
+}
+
+/// ANSI colored view of a schematic.
+///
+/// Part numbers are green, numbers touching no symbol red, gears (symbols a
+/// rule in `rules` accepts) bold yellow with their numbers in yellow, and any
+/// other symbol cyan.
+pub struct Renderer<'a> {
+    schematic: &'a Schematic,
+    gears: Vec<bool>,
+    gear_numbers: Vec<bool>,
+    viewport: Option<Viewport>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(schematic: &'a Schematic, rules: &RuleSet) -> Self {
+        let mut gears = vec![false; schematic.symbols.len()];
+        let mut gear_numbers = vec![false; schematic.numbers.len()];
+        for (symbol_idx, is_gear) in gears.iter_mut().enumerate() {
+            if rules.rule_for(schematic, symbol_idx).is_some() {
+                *is_gear = true;
+                for &number_idx in &schematic.symbol_numbers[symbol_idx] {
+                    gear_numbers[number_idx] = true;
+                }
+            }
+        }
+
+        Self {
+            schematic,
+            gears,
+            gear_numbers,
+            viewport: None,
+        }
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    fn color(&self, x: usize, y: usize, c: char) -> &'static str {
+        if let Some(number_idx) = self.schematic.number_idx_at(x, y) {
+            if self.gear_numbers[number_idx] {
+                GEAR_NUMBER
+            } else if self.schematic.is_part_number(number_idx) {
+                PART_NUMBER
+            } else {
+                LOOSE_NUMBER
+            }
+        } else if c == '.' {
+            EMPTY
+        } else {
+            let symbol_idx = self
+                .schematic
+                .symbols
+                .binary_search_by_key(&(y, x), |s| (s.y, s.x))
+                .expect("every non-digit, non-dot cell is a symbol");
+            if self.gears[symbol_idx] {
+                GEAR
+            } else {
+                SYMBOL
+            }
+        }
+    }
+}
+
+impl fmt::Display for Renderer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let grid = &self.schematic.grid;
+        let viewport = self.viewport.unwrap_or(Viewport {
+            x: 0,
+            y: 0,
+            width: grid.width,
+            height: grid.height,
+        });
+        let x_end = viewport.x.saturating_add(viewport.width).min(grid.width);
+        let y_end = viewport.y.saturating_add(viewport.height).min(grid.height);
+
+        for y in viewport.y..y_end {
+            for x in viewport.x..x_end {
+                let c = grid.data[y][x];
+                write!(f, "{}{}{} ", self.color(x, y, c), c, RESET)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
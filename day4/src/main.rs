@@ -1,11 +1,13 @@
 use nom::{
+    branch::alt,
     bytes::complete::tag,
     character::complete::{digit1, multispace1},
-    combinator::{map, map_res},
+    combinator::{map, map_res, value, verify},
     multi::separated_list1,
     sequence::{preceded, separated_pair, tuple},
     IResult,
 };
+use num::{BigUint, One, Zero};
 
 static EXAMPLE_INPUT: &str = r#"Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
 Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
@@ -16,74 +18,341 @@ Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11"#;
 
 #[derive(Debug)]
 struct Card {
-    matches: u32,
+    id: u32,
+    winning: Vec<u32>,
+    held: Vec<u32>,
 }
 
 impl Card {
     fn parse(i: &str) -> IResult<&str, Self> {
         map(
-            preceded(
-                tuple((
-                    tag("Card"),
-                    multispace1,
-                    parse_number,
-                    tag(":"),
-                    multispace1,
-                )),
-                separated_pair(
-                    separated_list1(multispace1, parse_number),
-                    tuple((multispace1, tag("|"), multispace1)),
-                    separated_list1(multispace1, parse_number),
+            tuple((
+                preceded(tuple((tag("Card"), multispace1)), parse_number),
+                preceded(
+                    tuple((tag(":"), multispace1)),
+                    separated_pair(
+                        separated_list1(multispace1, parse_number),
+                        tuple((multispace1, tag("|"), multispace1)),
+                        separated_list1(multispace1, parse_number),
+                    ),
                 ),
-            ),
-            |(winning, my)| Self {
-                matches: my.iter().filter(|&&num| winning.contains(&num)).count() as u32,
-            },
+            )),
+            |(id, (winning, held))| Self { id, winning, held },
         )(i)
     }
+
+    fn matches(&self) -> usize {
+        self.held
+            .iter()
+            .filter(|num| self.winning.contains(num))
+            .count()
+    }
 }
 
-fn main() {
-    println!("-- Advent of Code 2023 - Day 4 --");
+/// How many points a card is worth for a given number of matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Scoring {
+    /// 1 point for the first match, doubled for every match after it.
+    Doubling,
+    /// 1 point per match.
+    Linear,
+    /// The n:th Fibonacci number for n matches.
+    Fibonacci,
+    /// `table[n]` points for n matches, the last entry for anything past the end.
+    Table(Vec<u32>),
+}
+
+impl Scoring {
+    fn score(&self, matches: usize) -> BigUint {
+        match self {
+            Scoring::Doubling if matches == 0 => BigUint::zero(),
+            Scoring::Doubling => BigUint::one() << (matches - 1),
+            Scoring::Linear => BigUint::from(matches),
+            Scoring::Fibonacci => {
+                let (mut a, mut b) = (BigUint::zero(), BigUint::one());
+                for _ in 0..matches {
+                    let next = &a + &b;
+                    a = std::mem::replace(&mut b, next);
+                }
+                a
+            }
+            Scoring::Table(table) => table
+                .get(matches)
+                .or(table.last())
+                .map(|&points| BigUint::from(points))
+                .unwrap_or_default(),
+        }
+    }
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
+    fn parse(i: &str) -> IResult<&str, Self> {
+        alt((
+            value(Self::Doubling, tag("doubling")),
+            value(Self::Linear, tag("linear")),
+            value(Self::Fibonacci, tag("fibonacci")),
+            map(
+                preceded(tag("table:"), separated_list1(tag(","), parse_number)),
+                Self::Table,
+            ),
+        ))(i)
+    }
+}
 
-    part1(input);
-    part2(input);
+/// Which cards below a winning card receive its copies. A card with n matches
+/// always wins n copies; the rule only decides where they land.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CopyRule {
+    /// The next n cards.
+    Next,
+    /// Every k:th card below it, n times.
+    EveryNth(usize),
 }
 
-fn part1(input: &str) {
-    let sum = input
-        .lines()
-        .map(|line| Card::parse(line).unwrap().1)
-        .map(|card| {
-            if card.matches > 0 {
-                2_u32.pow(card.matches - 1)
-            } else {
-                0
+impl CopyRule {
+    fn stride(&self) -> usize {
+        match self {
+            CopyRule::Next => 1,
+            CopyRule::EveryNth(k) => *k,
+        }
+    }
+
+    fn parse(i: &str) -> IResult<&str, Self> {
+        alt((
+            value(Self::Next, tag("next")),
+            // a stride of 0 would hand every copy back to the same card
+            map(
+                verify(preceded(tag("every:"), parse_number), |&k| k > 0),
+                |k| Self::EveryNth(k as usize),
+            ),
+        ))(i)
+    }
+}
+
+/// Result of letting every card win copies of the cards below it.
+#[derive(Debug)]
+struct Cascade {
+    /// Number of instances held of each card, original included.
+    copies: Vec<BigUint>,
+    /// Cards whose win range ran past the last card, with how many wins were dropped.
+    clamped: Vec<(usize, usize)>,
+}
+
+impl Cascade {
+    fn run(cards: &[Card], rule: CopyRule) -> Self {
+        let stride = rule.stride();
+        assert!(stride > 0, "copy stride must be at least 1");
+
+        // copies won by card i apply to cards `i + stride, i + 2 * stride, ..= end`, so
+        // rather than adding to each of them we record where the run starts and where it
+        // stops, and carry a running total along every stride
+        let mut starting = vec![BigUint::zero(); cards.len()];
+        let mut ending = vec![BigUint::zero(); cards.len() + stride];
+        let mut won: Vec<BigUint> = Vec::with_capacity(cards.len());
+        let mut copies = Vec::with_capacity(cards.len());
+        let mut clamped = Vec::new();
+
+        for (i, card) in cards.iter().enumerate() {
+            let mut carried = match i.checked_sub(stride) {
+                Some(prev) => won[prev].clone(),
+                None => BigUint::zero(),
+            };
+            carried += &starting[i];
+            carried -= &ending[i];
+            let count = &carried + 1_u32;
+            won.push(carried);
+
+            let matches = card.matches();
+            let reachable = (cards.len() - 1 - i) / stride;
+            if matches > reachable {
+                clamped.push((i, matches - reachable));
+            }
+            let wins = matches.min(reachable);
+            if wins > 0 {
+                starting[i + stride] += &count;
+                ending[i + (wins + 1) * stride] += &count;
             }
-        })
-        .sum::<u32>();
 
-    println!("Part 1: {}", sum);
+            copies.push(count);
+        }
+
+        Self { copies, clamped }
+    }
+
+    fn total(&self) -> BigUint {
+        self.copies.iter().sum()
+    }
 }
 
-fn part2(input: &str) {
+fn main() {
+    println!("-- Advent of Code 2023 - Day 4 --");
+
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
+
     let cards = input
         .lines()
         .map(|line| Card::parse(line).unwrap().1)
         .collect::<Vec<_>>();
 
-    let mut cards_count = vec![1; cards.len()];
-    for (i, card) in cards.iter().enumerate() {
-        let count = cards_count[i];
-        (i..i + card.matches as usize).for_each(|j| cards_count[j + 1] += count);
+    part1(&cards);
+    part2(&cards);
+
+    let scoring = arg_value("--scoring").map(|spec| {
+        let (rest, scoring) = Scoring::parse(&spec).expect("invalid scoring model");
+        assert!(
+            rest.is_empty(),
+            "unexpected trailing scoring input: {}",
+            rest
+        );
+        scoring
+    });
+    let rule = arg_value("--copy-rule").map(|spec| {
+        let (rest, rule) = CopyRule::parse(&spec).expect("invalid copy rule");
+        assert!(
+            rest.is_empty(),
+            "unexpected trailing copy rule input: {}",
+            rest
+        );
+        rule
+    });
+    if scoring.is_some() || rule.is_some() {
+        variant(
+            &cards,
+            &scoring.unwrap_or(Scoring::Doubling),
+            rule.unwrap_or(CopyRule::Next),
+        );
+    }
+}
+
+fn part1(cards: &[Card]) {
+    let sum = cards
+        .iter()
+        .map(|card| Scoring::Doubling.score(card.matches()))
+        .sum::<BigUint>();
+
+    println!("Part 1: {}", sum);
+}
+
+fn part2(cards: &[Card]) {
+    let cascade = Cascade::run(cards, CopyRule::Next);
+    for (i, dropped) in &cascade.clamped {
+        println!(
+            "Warning: card {} wins {} cards past the end of the table",
+            cards[*i].id, dropped
+        );
+    }
+    if std::env::args().any(|a| a == "--copies") {
+        for (card, count) in cards.iter().zip(&cascade.copies) {
+            println!("Card {}: {}", card.id, count);
+        }
     }
 
-    println!("Part 2: {}", cards_count.iter().sum::<u32>());
+    println!("Part 2: {}", cascade.total());
+}
+
+fn variant(cards: &[Card], scoring: &Scoring, rule: CopyRule) {
+    let points = cards
+        .iter()
+        .map(|card| scoring.score(card.matches()))
+        .sum::<BigUint>();
+    let cascade = Cascade::run(cards, rule);
+
+    println!("Variant ({:?}, {:?}):", scoring, rule);
+    println!("  Points: {}", points);
+    println!(
+        "  Cards: {} ({} clamped)",
+        cascade.total(),
+        cascade.clamped.len()
+    );
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|a| a != name).nth(1)
 }
 
 fn parse_number(i: &str) -> IResult<&str, u32> {
     map_res(digit1, |s: &str| s.parse::<u32>())(i)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(matches: u32) -> Card {
+        Card {
+            id: 0,
+            winning: (0..matches).collect(),
+            held: (0..matches).collect(),
+        }
+    }
+
+    #[test]
+    fn test_cascade_clamps_past_last_card() {
+        let cards = [card(3), card(1)];
+        let cascade = Cascade::run(&cards, CopyRule::Next);
+
+        assert_eq!(
+            cascade.copies,
+            vec![BigUint::from(1_u32), BigUint::from(2_u32)]
+        );
+        assert_eq!(cascade.clamped, vec![(0, 2), (1, 1)]);
+    }
+
+    #[test]
+    fn test_cascade_does_not_overflow() {
+        let cards = (0..200).map(|i| card(199 - i)).collect::<Vec<_>>();
+        let cascade = Cascade::run(&cards, CopyRule::Next);
+
+        assert_eq!(cascade.total(), (BigUint::one() << 200) - 1_u32);
+        assert!(cascade.clamped.is_empty());
+    }
+
+    #[test]
+    fn test_cascade_every_nth() {
+        let cards = [card(2), card(0), card(1), card(0), card(0)];
+        let cascade = Cascade::run(&cards, CopyRule::EveryNth(2));
+
+        let copies = cascade
+            .copies
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(copies, ["1", "1", "2", "1", "4"]);
+    }
+
+    #[test]
+    fn test_parse_copy_rule() {
+        assert_eq!(CopyRule::parse("next"), Ok(("", CopyRule::Next)));
+        assert_eq!(CopyRule::parse("every:3"), Ok(("", CopyRule::EveryNth(3))));
+        assert!(CopyRule::parse("every:0").is_err());
+        assert_eq!(
+            CopyRule::parse("every:2junk"),
+            Ok(("junk", CopyRule::EveryNth(2)))
+        );
+    }
+
+    #[test]
+    fn test_scoring() {
+        let scores = |scoring: Scoring| {
+            (0..7)
+                .map(|m| scoring.score(m).to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            scores(Scoring::Doubling),
+            ["0", "1", "2", "4", "8", "16", "32"]
+        );
+        assert_eq!(scores(Scoring::Linear), ["0", "1", "2", "3", "4", "5", "6"]);
+        assert_eq!(
+            scores(Scoring::Fibonacci),
+            ["0", "1", "1", "2", "3", "5", "8"]
+        );
+        assert_eq!(
+            scores(Scoring::Table(vec![0, 10, 15])),
+            ["0", "10", "15", "15", "15", "15", "15"]
+        );
+    }
+}
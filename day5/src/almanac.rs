@@ -0,0 +1,368 @@
+use std::{collections::HashMap, fmt, ops::Range};
+
+use crate::piecewise::PiecewiseMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlmanacError {
+    MissingSeeds,
+    BadHeader(String),
+    BadLine(String),
+    /// Two sections convert from the same category.
+    DuplicateSource(String),
+    /// Two sections convert into the same category.
+    DuplicateTarget(String),
+    /// The sections don't form a single unbroken chain, e.g. `soil` is never
+    /// converted into anything but `water` is converted from something.
+    BrokenChain {
+        from: String,
+        to: String,
+    },
+    UnknownCategory(String),
+    NotInvertible {
+        from: String,
+        to: String,
+    },
+    /// Strict parsing found lines that disagree on where a value goes.
+    Ambiguous(Vec<Diagnostic>),
+}
+
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlmanacError::MissingSeeds => write!(f, "missing `seeds:` line"),
+            AlmanacError::BadHeader(header) => {
+                write!(f, "expected `X-to-Y map:`, got `{}`", header)
+            }
+            AlmanacError::BadLine(line) => write!(f, "expected three numbers, got `{}`", line),
+            AlmanacError::DuplicateSource(category) => {
+                write!(f, "more than one map converts from `{}`", category)
+            }
+            AlmanacError::DuplicateTarget(category) => {
+                write!(f, "more than one map converts into `{}`", category)
+            }
+            AlmanacError::BrokenChain { from, to } => {
+                write!(f, "no chain of maps connects `{}` and `{}`", from, to)
+            }
+            AlmanacError::UnknownCategory(category) => write!(f, "unknown category `{}`", category),
+            AlmanacError::NotInvertible { from, to } => {
+                write!(
+                    f,
+                    "`{}` to `{}` is not one-to-one, so it can't be reversed",
+                    from, to
+                )
+            }
+            AlmanacError::Ambiguous(diagnostics) => {
+                write!(f, "ambiguous almanac")?;
+                for diagnostic in diagnostics.iter().filter(|d| d.issue.is_ambiguous()) {
+                    write!(f, "\n  {}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AlmanacError {}
+
+/// Something questionable about the lines of one section. Line numbers are
+/// 1-based and count from the top of the almanac.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// Part of `line`'s source range is also covered by the earlier `other`.
+    Overlap {
+        line: usize,
+        other: usize,
+        range: Range<i64>,
+    },
+    /// `line` has a length of zero and maps nothing.
+    Empty { line: usize },
+    /// No line covers `range`, which sits between `before` and `after`.
+    Gap {
+        before: usize,
+        after: usize,
+        range: Range<i64>,
+    },
+}
+
+impl Issue {
+    /// Whether the almanac can be read more than one way because of this.
+    /// Only overlaps are; gaps and empty lines just map values to themselves.
+    pub fn is_ambiguous(&self) -> bool {
+        matches!(self, Issue::Overlap { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub section: String,
+    pub issue: Issue,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.issue {
+            Issue::Overlap { line, other, range } => write!(
+                f,
+                "line {} ({}): {:?} overlaps line {}",
+                line, self.section, range, other
+            ),
+            Issue::Empty { line } => {
+                write!(f, "line {} ({}): zero-length range", line, self.section)
+            }
+            Issue::Gap {
+                before,
+                after,
+                range,
+            } => write!(
+                f,
+                "lines {} and {} ({}): {:?} between them is not covered",
+                before, after, self.section, range
+            ),
+        }
+    }
+}
+
+/// One `X-to-Y map:` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section<'a> {
+    pub source: &'a str,
+    pub target: &'a str,
+    pub lines: Vec<(Range<i64>, i64)>,
+    /// Line number of the header.
+    pub header_line: usize,
+}
+
+impl<'a> Section<'a> {
+    pub fn parse(input: &'a str, header_line: usize) -> Result<Self, AlmanacError> {
+        let mut lines = input.lines();
+        let header = lines.next().unwrap_or_default();
+        let (source, target) = header
+            .strip_suffix(" map:")
+            .and_then(|name| name.split_once("-to-"))
+            .ok_or_else(|| AlmanacError::BadHeader(header.to_string()))?;
+
+        let lines = lines
+            .map(|l| {
+                let numbers = l
+                    .split_whitespace()
+                    .map(|x| x.parse::<i64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()
+                    .filter(|numbers| numbers.len() == 3)
+                    .ok_or_else(|| AlmanacError::BadLine(l.to_string()))?;
+
+                let range = numbers[1]..numbers[1] + numbers[2];
+                let offset = numbers[0] - numbers[1];
+                Ok((range, offset))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            source,
+            target,
+            lines,
+            header_line,
+        })
+    }
+
+    fn line_number(&self, idx: usize) -> usize {
+        self.header_line + 1 + idx
+    }
+
+    /// Overlapping, empty and missing source ranges, found by sweeping the
+    /// lines in order of where they start.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        let mut sorted = Vec::with_capacity(self.lines.len());
+        for (idx, (range, _)) in self.lines.iter().enumerate() {
+            if range.is_empty() {
+                issues.push(Issue::Empty {
+                    line: self.line_number(idx),
+                });
+            } else {
+                sorted.push((range, idx));
+            }
+        }
+        sorted.sort_by_key(|(range, idx)| (range.start, *idx));
+
+        // lines still open at the current start, any of which the next line
+        // may overlap, and the line reaching furthest so far for gaps
+        let mut active: Vec<(&Range<i64>, usize)> = Vec::new();
+        let mut furthest: Option<(&Range<i64>, usize)> = None;
+        for (range, idx) in sorted {
+            active.retain(|(other, _)| other.end > range.start);
+            for &(other, other_idx) in &active {
+                // the line listed first wins the overlap
+                let (line, other_line) = if idx > other_idx {
+                    (idx, other_idx)
+                } else {
+                    (other_idx, idx)
+                };
+                issues.push(Issue::Overlap {
+                    line: self.line_number(line),
+                    other: self.line_number(other_line),
+                    range: range.start..range.end.min(other.end),
+                });
+            }
+
+            if let Some((reach, reach_idx)) = furthest {
+                if range.start > reach.end {
+                    issues.push(Issue::Gap {
+                        before: self.line_number(reach_idx),
+                        after: self.line_number(idx),
+                        range: reach.end..range.start,
+                    });
+                }
+            }
+            if furthest.is_none_or(|(reach, _)| range.end > reach.end) {
+                furthest = Some((range, idx));
+            }
+            active.push((range, idx));
+        }
+
+        issues
+    }
+}
+
+/// The seeds and a validated chain of category conversions.
+///
+/// `maps[i]` converts `categories[i]` into `categories[i + 1]`, regardless of
+/// the order the sections appeared in.
+#[derive(Debug)]
+pub struct Almanac<'a> {
+    pub seeds: Vec<i64>,
+    pub categories: Vec<&'a str>,
+    maps: Vec<PiecewiseMap>,
+    /// Issues found in the sections. Overlaps are resolved first line wins.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Almanac<'a> {
+    pub fn parse(input: &'a str) -> Result<Self, AlmanacError> {
+        let mut parts = input.split("\n\n");
+
+        let seeds = parts
+            .next()
+            .and_then(|l| l.strip_prefix("seeds:"))
+            .ok_or(AlmanacError::MissingSeeds)?
+            .split_whitespace()
+            .map(|x| x.parse().map_err(|_| AlmanacError::MissingSeeds))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let sections = parts
+            .map(|part| {
+                let offset = part.as_ptr() as usize - input.as_ptr() as usize;
+                Section::parse(part, input[..offset].matches('\n').count() + 1)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::chain(seeds, sections)
+    }
+
+    /// Like [`Almanac::parse`], but rejects almanacs where lines overlap.
+    pub fn parse_strict(input: &'a str) -> Result<Self, AlmanacError> {
+        let almanac = Self::parse(input)?;
+        if almanac.diagnostics.iter().any(|d| d.issue.is_ambiguous()) {
+            return Err(AlmanacError::Ambiguous(almanac.diagnostics));
+        }
+
+        Ok(almanac)
+    }
+
+    fn chain(seeds: Vec<i64>, sections: Vec<Section<'a>>) -> Result<Self, AlmanacError> {
+        let mut by_source = HashMap::new();
+        let mut targets = HashMap::new();
+        for (idx, section) in sections.iter().enumerate() {
+            if by_source.insert(section.source, idx).is_some() {
+                return Err(AlmanacError::DuplicateSource(section.source.to_string()));
+            }
+            if targets.insert(section.target, idx).is_some() {
+                return Err(AlmanacError::DuplicateTarget(section.target.to_string()));
+            }
+        }
+
+        let mut starts = sections
+            .iter()
+            .filter(|s| !targets.contains_key(s.source))
+            .map(|s| s.source);
+        let Some(start) = starts.next() else {
+            // every category is converted into, so the maps loop back on themselves
+            let category = sections.first().map(|s| s.source).unwrap_or_default();
+            return Err(AlmanacError::BrokenChain {
+                from: category.to_string(),
+                to: category.to_string(),
+            });
+        };
+        if let Some(other) = starts.next() {
+            return Err(AlmanacError::BrokenChain {
+                from: start.to_string(),
+                to: other.to_string(),
+            });
+        }
+
+        let mut categories = vec![start];
+        let mut maps = Vec::with_capacity(sections.len());
+        while let Some(&idx) = by_source.get(categories[categories.len() - 1]) {
+            let section = &sections[idx];
+            categories.push(section.target);
+            maps.push(PiecewiseMap::from_section(&section.lines));
+        }
+        if maps.len() != sections.len() {
+            // the leftover sections form a cycle detached from the main chain
+            let stray = sections
+                .iter()
+                .find(|s| !categories.contains(&s.source))
+                .expect("a section is missing from the chain");
+            return Err(AlmanacError::BrokenChain {
+                from: start.to_string(),
+                to: stray.source.to_string(),
+            });
+        }
+
+        let diagnostics = sections
+            .iter()
+            .flat_map(|section| {
+                section.validate().into_iter().map(|issue| Diagnostic {
+                    section: format!("{}-to-{}", section.source, section.target),
+                    issue,
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            seeds,
+            categories,
+            maps,
+            diagnostics,
+        })
+    }
+
+    fn category_idx(&self, category: &str) -> Result<usize, AlmanacError> {
+        self.categories
+            .iter()
+            .position(|&c| c == category)
+            .ok_or_else(|| AlmanacError::UnknownCategory(category.to_string()))
+    }
+
+    /// Map converting any category into any other. Going against the chain,
+    /// e.g. `location` to `seed`, needs every map in between to be one-to-one.
+    pub fn conversion(&self, from: &str, to: &str) -> Result<PiecewiseMap, AlmanacError> {
+        let from_idx = self.category_idx(from)?;
+        let to_idx = self.category_idx(to)?;
+
+        let compose = |maps: &[PiecewiseMap]| {
+            maps.iter()
+                .fold(PiecewiseMap::identity(), |composed, map| composed.then(map))
+        };
+        if from_idx <= to_idx {
+            Ok(compose(&self.maps[from_idx..to_idx]))
+        } else {
+            compose(&self.maps[to_idx..from_idx])
+                .invert()
+                .ok_or_else(|| AlmanacError::NotInvertible {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                })
+        }
+    }
+}
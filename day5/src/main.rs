@@ -1,6 +1,7 @@
-use std::ops::Range;
+use almanac::Almanac;
 
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+mod almanac;
+mod piecewise;
 
 static EXAMPLE_INPUT: &str = r#"seeds: 79 14 55 13
 
@@ -39,88 +40,263 @@ humidity-to-location map:
 fn main() {
     println!("-- Advent of Code 2023 - Day 5 --");
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
+    let almanac = if std::env::args().any(|a| a == "--strict") {
+        Almanac::parse_strict(input)
+    } else {
+        Almanac::parse(input)
+    }
+    .unwrap_or_else(|err| panic!("invalid almanac: {}", err));
+    // gaps and empty lines are harmless, so only show them when asked
+    let validate = std::env::args().any(|a| a == "--validate");
+    for diagnostic in &almanac.diagnostics {
+        if diagnostic.issue.is_ambiguous() {
+            println!("Warning: {}", diagnostic);
+        } else if validate {
+            println!("Note: {}", diagnostic);
+        }
+    }
 
     let now = std::time::Instant::now();
-    part1(input);
+    part1(&almanac);
     println!("Time: {}ms", (now.elapsed().as_micros() as f64 / 1000.0));
     let now = std::time::Instant::now();
-    part2(input);
-    println!("Time: {}s", now.elapsed().as_millis() as f64 / 1000.0);
-}
-
-fn part1(input: &str) {
-    let mut parts = input.split("\n\n");
+    part2(&almanac);
+    println!("Time: {}µs", now.elapsed().as_micros());
 
-    let seeds: Vec<i64> = parts
-        .next()
-        .unwrap()
-        .split_whitespace()
-        .skip(1)
-        .map(|x| x.parse().unwrap())
-        .collect::<Vec<_>>();
+    let mut args = std::env::args().skip_while(|a| a != "--convert").skip(1);
+    if let (Some(from), Some(to), Some(value)) = (args.next(), args.next(), args.next()) {
+        convert(
+            &almanac,
+            &from,
+            &to,
+            value.parse().expect("value should be a number"),
+        );
+    }
+}
 
-    let sections: Vec<_> = parts.map(parse_sections).collect();
-    let min_location = seeds
-        .into_iter()
-        .map(|seed| {
-            sections.iter().fold(seed, |seed, section| {
-                section
-                    .iter()
-                    .find_map(|(range, offset)| range.contains(&seed).then_some(seed + offset))
-                    .unwrap_or(seed)
-            })
-        })
+fn part1(almanac: &Almanac) {
+    let seed_to_location = almanac.conversion("seed", "location").unwrap();
+    let min_location = almanac
+        .seeds
+        .iter()
+        .map(|&seed| seed_to_location.apply(seed))
         .min()
         .unwrap();
 
     println!("Part 1: {}", min_location);
 }
 
-fn part2(input: &str) {
-    let mut parts = input.split("\n\n");
-
-    let seeds: Vec<(i64, i64)> = parts
-        .next()
-        .unwrap()
-        .split_whitespace()
-        .skip(1)
-        .map(|x| x.parse().unwrap())
-        .collect::<Vec<_>>()
-        .chunks(2)
-        .map(|s| (s[0], s[1]))
-        .collect();
-
-    let sections: Vec<_> = parts.map(parse_sections).collect();
-    let min_location = seeds
-        .into_par_iter()
-        .flat_map(|(start, len)| {
-            (start..start + len).into_par_iter().map(|seed| {
-                sections.iter().fold(seed, |seed, section| {
-                    section
-                        .iter()
-                        .find_map(|(range, offset)| range.contains(&seed).then_some(seed + offset))
-                        .unwrap_or(seed)
-                })
-            })
-        })
+fn part2(almanac: &Almanac) {
+    let seed_to_location = almanac.conversion("seed", "location").unwrap();
+    let ranges: Vec<_> = almanac.seeds.chunks(2).map(|s| s[0]..s[0] + s[1]).collect();
+    let min_location = seed_to_location
+        .map_ranges(&ranges)
+        .into_iter()
+        .map(|range| range.start)
         .min()
         .unwrap();
 
     println!("Part 2: {}", min_location);
+
+    let seeds = match seed_to_location.invert() {
+        Some(inverse) => vec![inverse.apply(min_location)],
+        None => seed_to_location.preimage(min_location),
+    };
+    let seeds = seeds
+        .into_iter()
+        .filter(|seed| ranges.iter().any(|r| r.contains(seed)))
+        .collect::<Vec<_>>();
+    println!(
+        "Lowest location comes from seed {:?} ({} breakpoints in the seed-to-location map)",
+        seeds,
+        seed_to_location.breakpoints().count()
+    );
 }
 
-fn parse_sections(input: &str) -> Vec<(Range<i64>, i64)> {
-    input
-        .lines()
-        .skip(1)
-        .map(|l| {
-            let numbers: Vec<i64> = l.split_whitespace().map(|x| x.parse().unwrap()).collect();
+fn convert(almanac: &Almanac, from: &str, to: &str, value: i64) {
+    match almanac.conversion(from, to) {
+        Ok(map) => match map.checked_apply(value) {
+            Some(converted) => println!("{} {} is {} {}", from, value, to, converted),
+            None => println!(
+                "Can't convert {} {}: outside the range of the maps",
+                from, value
+            ),
+        },
+        Err(err) => println!("Can't convert {} to {}: {}", from, to, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ops::Range;
+
+    use crate::{
+        almanac::{AlmanacError, Issue, Section},
+        piecewise::PiecewiseMap,
+    };
 
-            let range = numbers[1]..numbers[1] + numbers[2];
-            let offset = numbers[0] - numbers[1];
-            (range, offset)
+    fn lookup(sections: &[Vec<(Range<i64>, i64)>], seed: i64) -> i64 {
+        sections.iter().fold(seed, |seed, section| {
+            section
+                .iter()
+                .find_map(|(range, offset)| range.contains(&seed).then_some(seed + offset))
+                .unwrap_or(seed)
         })
-        .collect()
+    }
+
+    #[test]
+    fn test_composed_matches_lookup() {
+        let sections: Vec<_> = EXAMPLE_INPUT
+            .split("\n\n")
+            .skip(1)
+            .map(|s| Section::parse(s, 0).unwrap().lines)
+            .collect();
+        let almanac = Almanac::parse(EXAMPLE_INPUT)
+            .unwrap()
+            .conversion("seed", "location")
+            .unwrap();
+
+        for seed in -10..120 {
+            assert_eq!(
+                almanac.apply(seed),
+                lookup(&sections, seed),
+                "seed {}",
+                seed
+            );
+        }
+        let mapped = almanac.map_ranges(&[79..93, 55..68]);
+        assert_eq!(mapped.iter().map(|r| r.start).min(), Some(46));
+    }
+
+    #[test]
+    fn test_invert() {
+        let almanac = Almanac::parse(EXAMPLE_INPUT)
+            .unwrap()
+            .conversion("seed", "location")
+            .unwrap();
+        let inverse = almanac.invert().unwrap();
+
+        for seed in -10..120 {
+            assert_eq!(inverse.apply(almanac.apply(seed)), seed);
+            assert_eq!(almanac.preimage(almanac.apply(seed)), vec![seed]);
+        }
+    }
+
+    #[test]
+    fn test_overlapping_lines() {
+        let map = PiecewiseMap::from_section(&[(0..10, 100), (5..15, 200)]);
+
+        assert!(map.invert().is_none());
+        assert_eq!(map.breakpoints().collect::<Vec<_>>(), vec![0, 10, 15]);
+        assert_eq!(map.apply(7), 107);
+        assert_eq!(map.apply(12), 212);
+        assert_eq!(map.checked_apply(i64::MAX - 1), Some(i64::MAX - 1));
+        assert_eq!(map.checked_apply(i64::MAX), None);
+        assert_eq!(map.preimage(107), vec![7, 107]);
+        assert!(map.preimage(5).is_empty());
+    }
+
+    #[test]
+    fn test_category_chain() {
+        // same almanac with the sections shuffled
+        let mut sections = EXAMPLE_INPUT.split("\n\n").collect::<Vec<_>>();
+        sections[1..].reverse();
+        let shuffled = sections.join("\n\n");
+
+        let almanac = Almanac::parse(&shuffled).unwrap();
+        assert_eq!(almanac.categories.first(), Some(&"seed"));
+        assert_eq!(almanac.categories.last(), Some(&"location"));
+        assert_eq!(
+            almanac.conversion("soil", "humidity").unwrap().apply(81),
+            78
+        );
+        assert_eq!(
+            almanac.conversion("humidity", "soil").unwrap().apply(78),
+            81
+        );
+
+        let broken = EXAMPLE_INPUT.replace("water-to-light", "water-to-sunlight");
+        assert!(matches!(
+            Almanac::parse(&broken),
+            Err(AlmanacError::BrokenChain { .. })
+        ));
+
+        let duplicate = EXAMPLE_INPUT.replace("soil-to-fertilizer", "seed-to-fertilizer");
+        assert_eq!(
+            Almanac::parse(&duplicate).unwrap_err(),
+            AlmanacError::DuplicateSource("seed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let section =
+            Section::parse("a-to-b map:\n10 0 5\n20 3 4\n30 10 0\n40 12 2\n50 1 2", 3).unwrap();
+
+        assert_eq!(
+            section.validate(),
+            vec![
+                Issue::Empty { line: 6 },
+                Issue::Overlap {
+                    line: 8,
+                    other: 4,
+                    range: 1..3
+                },
+                Issue::Overlap {
+                    line: 5,
+                    other: 4,
+                    range: 3..5
+                },
+                Issue::Gap {
+                    before: 5,
+                    after: 7,
+                    range: 7..12
+                },
+            ]
+        );
+
+        // the last line overlaps both lines still open when it starts
+        let section = Section::parse("a-to-b map:\n0 2 2\n0 0 10\n0 3 2", 0).unwrap();
+        assert_eq!(
+            section.validate(),
+            vec![
+                Issue::Overlap {
+                    line: 2,
+                    other: 1,
+                    range: 2..4
+                },
+                Issue::Overlap {
+                    line: 3,
+                    other: 2,
+                    range: 3..5
+                },
+                Issue::Overlap {
+                    line: 3,
+                    other: 1,
+                    range: 3..4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strict() {
+        let almanac = Almanac::parse(EXAMPLE_INPUT).unwrap();
+        assert!(almanac.diagnostics.iter().all(|d| !d.issue.is_ambiguous()));
+        assert!(Almanac::parse_strict(EXAMPLE_INPUT).is_ok());
+
+        let overlapping = EXAMPLE_INPUT.replace("50 98 2", "50 97 3");
+        let err = Almanac::parse_strict(&overlapping).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ambiguous almanac\n  line 5 (seed-to-soil): 97..98 overlaps line 4"
+        );
+    }
 }
@@ -0,0 +1,198 @@
+use std::ops::Range;
+
+/// Full domain a [`PiecewiseMap`] is defined over. `i64::MAX` itself is left
+/// out so the domain fits in a `Range`.
+const DOMAIN: Range<i64> = i64::MIN..i64::MAX;
+
+/// A run of consecutive inputs that are all shifted by the same offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    pub source: Range<i64>,
+    pub offset: i64,
+}
+
+impl Piece {
+    fn target(&self) -> Range<i64> {
+        self.source.start + self.offset..self.source.end + self.offset
+    }
+}
+
+/// Piecewise translation defined on every `i64` but `i64::MAX`.
+///
+/// Pieces are sorted, disjoint and cover the whole domain, with values no
+/// almanac line mentions mapping to themselves. Neighbouring pieces never share
+/// an offset, so every piece start after the first is a real breakpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiecewiseMap {
+    pieces: Vec<Piece>,
+}
+
+impl PiecewiseMap {
+    pub fn identity() -> Self {
+        Self {
+            pieces: vec![Piece {
+                source: DOMAIN,
+                offset: 0,
+            }],
+        }
+    }
+
+    /// Builds the map for one almanac section. Where lines overlap the first
+    /// one listed wins, same as looking values up line by line.
+    pub fn from_section(section: &[(Range<i64>, i64)]) -> Self {
+        let mut pieces: Vec<(Range<i64>, Option<i64>)> = vec![(DOMAIN, None)];
+
+        for (source, offset) in section {
+            let mut split = Vec::with_capacity(pieces.len() + 2);
+            for (range, assigned) in pieces {
+                let start = range.start.max(source.start);
+                let end = range.end.min(source.end);
+                if assigned.is_some() || start >= end {
+                    split.push((range, assigned));
+                    continue;
+                }
+
+                if range.start < start {
+                    split.push((range.start..start, None));
+                }
+                split.push((start..end, Some(*offset)));
+                if end < range.end {
+                    split.push((end..range.end, None));
+                }
+            }
+            pieces = split;
+        }
+
+        Self::normalized(
+            pieces
+                .into_iter()
+                .map(|(source, offset)| Piece {
+                    source,
+                    offset: offset.unwrap_or(0),
+                })
+                .collect(),
+        )
+    }
+
+    fn normalized(mut pieces: Vec<Piece>) -> Self {
+        pieces.sort_by_key(|p| p.source.start);
+
+        let mut merged: Vec<Piece> = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            match merged.last_mut() {
+                Some(last)
+                    if last.offset == piece.offset && last.source.end == piece.source.start =>
+                {
+                    last.source.end = piece.source.end;
+                }
+                _ => merged.push(piece),
+            }
+        }
+
+        Self { pieces: merged }
+    }
+
+    /// Inputs where the offset changes.
+    pub fn breakpoints(&self) -> impl Iterator<Item = i64> + '_ {
+        self.pieces.iter().skip(1).map(|p| p.source.start)
+    }
+
+    /// Index of the piece containing `x`.
+    fn piece_idx(&self, x: i64) -> usize {
+        self.pieces.partition_point(|p| p.source.end <= x)
+    }
+
+    pub fn apply(&self, x: i64) -> i64 {
+        self.checked_apply(x)
+            .unwrap_or_else(|| panic!("{} is outside the map's domain", x))
+    }
+
+    /// Like [`PiecewiseMap::apply`], but `None` for `i64::MAX` or if the
+    /// result would overflow.
+    pub fn checked_apply(&self, x: i64) -> Option<i64> {
+        if !DOMAIN.contains(&x) {
+            return None;
+        }
+        x.checked_add(self.pieces[self.piece_idx(x)].offset)
+    }
+
+    /// Images of whole input ranges, split wherever they cross a breakpoint.
+    pub fn map_ranges(&self, ranges: &[Range<i64>]) -> Vec<Range<i64>> {
+        let mut mapped = Vec::new();
+
+        for range in ranges.iter().filter(|r| !r.is_empty()) {
+            for piece in &self.pieces[self.piece_idx(range.start)..] {
+                if piece.source.start >= range.end {
+                    break;
+                }
+
+                let start = range.start.max(piece.source.start);
+                let end = range.end.min(piece.source.end);
+                mapped.push(start + piece.offset..end + piece.offset);
+            }
+        }
+
+        mapped
+    }
+
+    /// The map applying `self` first and then `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        let mut pieces = Vec::new();
+
+        for piece in &self.pieces {
+            let target = piece.target();
+            for next_piece in &next.pieces[next.piece_idx(target.start)..] {
+                if next_piece.source.start >= target.end {
+                    break;
+                }
+
+                let start = target.start.max(next_piece.source.start);
+                let end = target.end.min(next_piece.source.end);
+                pieces.push(Piece {
+                    source: start - piece.offset..end - piece.offset,
+                    offset: piece.offset + next_piece.offset,
+                });
+            }
+        }
+
+        Self::normalized(pieces)
+    }
+
+    /// The inverse map, if every output has exactly one input.
+    pub fn invert(&self) -> Option<Self> {
+        let mut inverse = self
+            .pieces
+            .iter()
+            .map(|p| Piece {
+                source: p.target(),
+                offset: -p.offset,
+            })
+            .collect::<Vec<_>>();
+        inverse.sort_by_key(|p| p.source.start);
+
+        let mut covered = DOMAIN.start;
+        for piece in &inverse {
+            if piece.source.start != covered {
+                return None;
+            }
+            covered = piece.source.end;
+        }
+        if covered != DOMAIN.end {
+            return None;
+        }
+
+        Some(Self::normalized(inverse))
+    }
+
+    /// Every input mapping to `y`, in ascending order.
+    pub fn preimage(&self, y: i64) -> Vec<i64> {
+        let mut inputs = self
+            .pieces
+            .iter()
+            .filter(|p| p.target().contains(&y))
+            .map(|p| y - p.offset)
+            .collect::<Vec<_>>();
+        inputs.sort_unstable();
+        inputs
+    }
+}
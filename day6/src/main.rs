@@ -1,14 +1,109 @@
 use itertools::Itertools;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use num::{integer::Roots, BigUint, Integer};
 
 static EXAMPLE_INPUT: &str = r#"Time:      7  15   30
 Distance:  9  40  200"#;
 
+/// How holding the button for `hold` ms turns into distance over the rest of
+/// the race. Every model rises to a single peak and then falls, which is all
+/// [`count_wins`] relies on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MotionModel {
+    /// The puzzle's rules: each ms held adds 1 mm/ms of speed.
+    LinearCharge,
+    /// Each ms held adds acceleration instead of speed, so the boat covers
+    /// `hold * t²` over the remaining `t` ms.
+    QuadraticAcceleration,
+    /// Like [`MotionModel::LinearCharge`], but speed stops growing at the cap.
+    CappedSpeed(u64),
+    /// The first ms held adds `rate` speed and every ms after it adds `decay`
+    /// less, until holding longer no longer helps.
+    ChargeDecay { rate: u64, decay: u64 },
+}
+
+impl MotionModel {
+    fn distance(&self, hold: u64, time: u64) -> u128 {
+        let moving = (time - hold) as u128;
+        let hold = hold as u128;
+
+        match *self {
+            MotionModel::LinearCharge => hold * moving,
+            MotionModel::QuadraticAcceleration => hold * moving * moving,
+            MotionModel::CappedSpeed(cap) => hold.min(cap as u128) * moving,
+            MotionModel::ChargeDecay { rate, decay } => {
+                let (rate, decay) = (rate as u128, decay as u128);
+                // number of held ms that still add speed
+                let charging = match decay {
+                    0 => hold,
+                    _ => hold.min(rate.div_ceil(decay)),
+                };
+                let speed = charging * rate - decay * charging * charging.saturating_sub(1) / 2;
+                speed * moving
+            }
+        }
+    }
+
+    /// Parses `linear`, `quadratic`, `capped:<cap>` or `decay:<rate>,<decay>`.
+    fn parse(s: &str) -> Option<Self> {
+        match s.split_once(':') {
+            None if s == "linear" => Some(Self::LinearCharge),
+            None if s == "quadratic" => Some(Self::QuadraticAcceleration),
+            Some(("capped", cap)) => Some(Self::CappedSpeed(cap.parse().ok()?)),
+            Some(("decay", args)) => {
+                let (rate, decay) = args.split_once(',')?;
+                Some(Self::ChargeDecay {
+                    rate: rate.parse().ok()?,
+                    decay: decay.parse().ok()?,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Number of hold times in `0..time` that beat `record` under `model`.
+///
+/// Since the distance rises to a peak and then falls, the winning holds form
+/// one interval around the peak, so three binary searches find it without
+/// trying every hold time.
+fn count_wins(model: &MotionModel, time: u64, record: u128) -> u64 {
+    let distance = |hold| model.distance(hold, time);
+    if time == 0 {
+        return 0;
+    }
+
+    let peak = first_where(0, time, |h| distance(h) >= distance(h + 1));
+    if distance(peak) <= record {
+        return 0;
+    }
+
+    let lowest = first_where(0, peak, |h| distance(h) > record);
+    let end = first_where(peak, time, |h| distance(h) <= record);
+    end - lowest
+}
+
+/// First value in `lo..hi` where `pred` holds, or `hi` if it never does.
+/// `pred` has to be false up to some point and true from then on.
+fn first_where(mut lo: u64, mut hi: u64, pred: impl Fn(u64) -> bool) -> u64 {
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    lo
+}
+
 fn main() {
     println!("\n-- Advent of Code 2023 - Day 6 --");
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
 
     // time them
     let now = std::time::Instant::now();
@@ -17,18 +112,23 @@ fn main() {
     let now = std::time::Instant::now();
     part2(input);
     println!("Time: {:?}", now.elapsed());
+
+    if let Some(spec) = std::env::args().skip_while(|a| a != "--model").nth(1) {
+        let model = MotionModel::parse(&spec).expect("unknown motion model");
+        variant(input, &model);
+    }
 }
 
 fn part1(input: &str) {
     let mut lines = input.lines();
-    let times: Vec<u32> = lines
+    let times: Vec<u64> = lines
         .next()
         .unwrap()
         .split_whitespace()
         .skip(1)
         .map(|s| s.parse().unwrap())
         .collect();
-    let distances: Vec<u32> = lines
+    let distances: Vec<u64> = lines
         .next()
         .unwrap()
         .split_whitespace()
@@ -36,57 +136,164 @@ fn part1(input: &str) {
         .map(|s| s.parse().unwrap())
         .collect();
 
-    let mut sum = 1;
     // figure out how many possible ways you can win for each race
-    for (time, distance) in times.iter().zip(distances.iter()) {
-        let mut possible_wins = 0;
-        for i in 0..*time {
-            let speed = i;
-            let time_to_move = *time - i;
-            let distance_moved = speed * time_to_move;
-            if distance_moved > *distance {
-                possible_wins += 1;
-            }
-        }
-        sum *= possible_wins;
-    }
+    let product = times
+        .iter()
+        .zip(distances.iter())
+        .map(|(time, distance)| winning_holds(time, distance))
+        .product::<u64>();
 
-    println!("Part 1: {}", sum);
+    println!("Part 1: {}", product);
 }
 
 fn part2(input: &str) {
     let mut lines = input.lines();
-    let time: u64 = lines
+    let time: BigUint = lines
         .next()
         .unwrap()
         .split_whitespace()
         .skip(1)
         .join("")
-        .parse::<u64>()
+        .parse()
         .unwrap();
-    let distance: u64 = lines
+    let distance: BigUint = lines
         .next()
         .unwrap()
         .split_whitespace()
         .skip(1)
         .join("")
-        .parse::<u64>()
+        .parse()
         .unwrap();
 
-    let possible_wins = (0..time)
-        .into_par_iter()
-        .map(|i| {
-            let speed = i;
-            let time_to_move = time - i;
-
-            let distance_moved = speed * time_to_move;
-            if distance_moved > distance {
-                1
-            } else {
-                0
+    println!("Part 2: {}", winning_holds(&time, &distance));
+}
+
+fn variant(input: &str, model: &MotionModel) {
+    let mut lines = input.lines();
+    let mut numbers = || -> Vec<u64> {
+        lines
+            .next()
+            .unwrap()
+            .split_whitespace()
+            .skip(1)
+            .map(|s| s.parse().unwrap())
+            .collect()
+    };
+    let (times, distances) = (numbers(), numbers());
+
+    let wins = times
+        .iter()
+        .zip(distances.iter())
+        .map(|(&time, &distance)| count_wins(model, time, distance as u128))
+        .collect::<Vec<_>>();
+
+    println!(
+        "\n{:?}: {:?} -> {}",
+        model,
+        wins,
+        wins.iter().product::<u64>()
+    );
+}
+
+/// Number of hold times `h` in `0..time` with `h * (time - h) > record`.
+///
+/// The winning holds are the integers strictly between the roots of
+/// `h² - time·h + record = 0`. The roots are found with an integer square
+/// root and then nudged onto the exact boundary, so there's no floating point
+/// rounding and any integer type that can hold `time²` works.
+fn winning_holds<T: Integer + Roots + Clone>(time: &T, record: &T) -> T {
+    let two = T::one() + T::one();
+    let four = two.clone() * two.clone();
+    let distance = |hold: &T| hold.clone() * (time.clone() - hold.clone());
+
+    let squared = time.clone() * time.clone();
+    let min_squared = four * record.clone();
+    if squared <= min_squared {
+        // the best hold time at most ties the record
+        return T::zero();
+    }
+
+    let root = (squared - min_squared).sqrt();
+    let half = time.clone() / two.clone();
+    let mut lowest = (time.clone() - root) / two.clone();
+    while lowest <= half && distance(&lowest) <= *record {
+        lowest = lowest + T::one();
+    }
+    if lowest > half {
+        return T::zero();
+    }
+    while !lowest.is_zero() && distance(&(lowest.clone() - T::one())) > *record {
+        lowest = lowest - T::one();
+    }
+
+    // wins are symmetric around time / 2, so the highest is `time - lowest`
+    time.clone() - two * lowest + T::one()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_brute_force() {
+        for time in 0..60_u64 {
+            for record in 0..=(time * time / 4 + 2) {
+                let expected = (0..time).filter(|h| h * (time - h) > record).count() as u64;
+                assert_eq!(
+                    winning_holds(&time, &record),
+                    expected,
+                    "{} {}",
+                    time,
+                    record
+                );
             }
-        })
-        .sum::<u64>();
+        }
+    }
+
+    #[test]
+    fn test_huge_race() {
+        let time = 3_000_000_000_000_000_000_u128;
+        let lowest = 1_000_000_000_000_000_000_u128;
+        // exactly ties at `lowest`, so the first win is one later
+        let record = lowest * (time - lowest);
 
-    println!("Part 2: {}", possible_wins);
+        let expected = time - 2 * (lowest + 1) + 1;
+        assert_eq!(winning_holds(&time, &record), expected);
+        assert_eq!(
+            winning_holds(&BigUint::from(time), &BigUint::from(record)),
+            BigUint::from(expected)
+        );
+    }
+
+    #[test]
+    fn test_motion_models() {
+        let models = [
+            MotionModel::LinearCharge,
+            MotionModel::QuadraticAcceleration,
+            MotionModel::CappedSpeed(7),
+            MotionModel::ChargeDecay { rate: 5, decay: 1 },
+            MotionModel::ChargeDecay { rate: 3, decay: 0 },
+        ];
+
+        for model in &models {
+            for time in 0..40 {
+                let best = (0..=time).map(|h| model.distance(h, time)).max().unwrap();
+                for record in 0..=best + 1 {
+                    let expected = (0..time)
+                        .filter(|&h| model.distance(h, time) > record)
+                        .count() as u64;
+                    assert_eq!(
+                        count_wins(model, time, record),
+                        expected,
+                        "{:?} {} {}",
+                        model,
+                        time,
+                        record
+                    );
+                }
+            }
+        }
+
+        assert_eq!(count_wins(&MotionModel::LinearCharge, 30, 200), 9);
+    }
 }
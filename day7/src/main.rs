@@ -1,4 +1,4 @@
-use std::cmp;
+use itertools::Itertools;
 
 static EXAMPLE_INPUT: &str = r#"32T3K 765
 T55J5 684
@@ -6,202 +6,625 @@ KK677 28
 KTJJT 220
 QQQJA 483"#;
 
-fn main() {
-    println!("\n-- Advent of Code 2023 - Day 7 --");
+/// The standard Camel Cards categories, weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum HandType {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    FullHouse,
+    FourOfAKind,
+    FiveOfAKind,
+}
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
+impl HandType {
+    const ALL: [HandType; 7] = [
+        HandType::HighCard,
+        HandType::OnePair,
+        HandType::TwoPair,
+        HandType::ThreeOfAKind,
+        HandType::FullHouse,
+        HandType::FourOfAKind,
+        HandType::FiveOfAKind,
+    ];
 
-    let start = std::time::Instant::now();
-    part1(input);
-    println!("Part 1: {}ms", start.elapsed().as_millis());
-    // part2(input);
+    fn name(self) -> &'static str {
+        match self {
+            HandType::HighCard => "High card",
+            HandType::OnePair => "One pair",
+            HandType::TwoPair => "Two pair",
+            HandType::ThreeOfAKind => "Three of a kind",
+            HandType::FullHouse => "Full house",
+            HandType::FourOfAKind => "Four of a kind",
+            HandType::FiveOfAKind => "Five of a kind",
+        }
+    }
+
+    fn groups(self) -> &'static [usize] {
+        match self {
+            HandType::HighCard => &[],
+            HandType::OnePair => &[2],
+            HandType::TwoPair => &[2, 2],
+            HandType::ThreeOfAKind => &[3],
+            HandType::FullHouse => &[3, 2],
+            HandType::FourOfAKind => &[4],
+            HandType::FiveOfAKind => &[5],
+        }
+    }
+
+    /// Classifies a hand from how many of each card it holds, plus some
+    /// jokers that join whichever group is already largest.
+    fn from_counts(counts: &[usize], jokers: usize) -> Self {
+        let mut counts = counts
+            .iter()
+            .copied()
+            .filter(|&c| c > 0)
+            .collect::<Vec<_>>();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        match counts.first_mut() {
+            Some(largest) => *largest += jokers,
+            None => counts.push(jokers),
+        }
+
+        match counts[..] {
+            [5, ..] => HandType::FiveOfAKind,
+            [4, ..] => HandType::FourOfAKind,
+            [3, 2, ..] => HandType::FullHouse,
+            [3, ..] => HandType::ThreeOfAKind,
+            [2, 2, ..] => HandType::TwoPair,
+            [2, ..] => HandType::OnePair,
+            _ => HandType::HighCard,
+        }
+    }
 }
 
-fn part1(input: &str) {
-    let card_ranking = vec![
-        'A', 'K', 'Q', 'T', '9', '8', '7', '6', '5', '4', '3', '2', 'J',
-    ];
+/// A kind of hand, e.g. full house, described by the groups of equal cards it
+/// needs: `[3, 2]` is satisfied by any hand with a group of at least three and
+/// another group of at least two.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Category {
+    name: String,
+    groups: Vec<usize>,
+}
 
-    let mut hands: Vec<(Vec<char>, usize)> = input
-        .lines()
-        .map(|line| {
-            let mut cards = line.split_whitespace();
-            let hand = cards.next().unwrap().chars().collect::<Vec<char>>();
-            let rank = cards.next().unwrap().parse::<usize>().unwrap();
-            (hand, rank)
-        })
-        .collect();
-
-    hands.sort_by(|(hand1, _), (hand2, _)| {
-        let hand1_rank = if try_with_every_joker(hand1, is_five_of_a_kind) {
-            1
-        } else if try_with_every_joker(hand1, is_four_of_a_kind) {
-            2
-        } else if try_with_every_joker(hand1, is_full_house) {
-            3
-        } else if try_with_every_joker(hand1, is_three_of_a_kind) {
-            4
-        } else if try_with_every_joker(hand1, is_two_pair) {
-            5
-        } else if try_with_every_joker(hand1, is_one_pair) {
-            6
-        } else if try_with_every_joker(hand1, is_high_card) {
-            7
-        } else {
-            panic!("Unknown hand: {:?}", hand1);
-        };
+impl Category {
+    fn new(name: &str, groups: &[usize]) -> Self {
+        Self {
+            name: name.to_string(),
+            groups: groups.to_vec(),
+        }
+    }
 
-        let hand2_rank = if try_with_every_joker(hand2, is_five_of_a_kind) {
-            1
-        } else if try_with_every_joker(hand2, is_four_of_a_kind) {
-            2
-        } else if try_with_every_joker(hand2, is_full_house) {
-            3
-        } else if try_with_every_joker(hand2, is_three_of_a_kind) {
-            4
-        } else if try_with_every_joker(hand2, is_two_pair) {
-            5
-        } else if try_with_every_joker(hand2, is_one_pair) {
-            6
-        } else if try_with_every_joker(hand2, is_high_card) {
-            7
-        } else {
-            panic!("Unknown hand: {:?}", hand2);
+    /// `groups` has to be sorted from largest to smallest.
+    fn matches(&self, groups: &[usize]) -> bool {
+        groups.len() >= self.groups.len()
+            && self
+                .groups
+                .iter()
+                .zip(groups)
+                .all(|(need, have)| have >= need)
+    }
+}
+
+/// A card that stands in for another when classifying a hand, but keeps its
+/// own strength when breaking ties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Wildcard {
+    symbol: char,
+    /// Cards it may become, or `None` for any card that isn't a wildcard.
+    substitutes: Option<Vec<char>>,
+}
+
+/// The category a hand reaches and the cards it was played as to get there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Classification {
+    category: usize,
+    /// The hand with every wildcard replaced by what it stood in for.
+    played: Vec<char>,
+}
+
+/// Everything that decides how Camel Cards hands rank.
+///
+/// Rulesets can be written as text, one setting per line:
+///
+/// ```text
+/// cards: 23456789TJQKA
+/// hand size: 5
+/// wild: J
+/// wild: * AKQ
+/// category: High card
+/// category: One pair 2
+/// category: Full house 3 2
+/// ```
+///
+/// `cards` and `category` lines go from weakest to strongest. A `wild` line
+/// without a card list can become any card.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ruleset {
+    /// Cards from weakest to strongest.
+    card_order: Vec<char>,
+    hand_size: usize,
+    wildcards: Vec<Wildcard>,
+    /// Categories from weakest to strongest.
+    categories: Vec<Category>,
+}
+
+impl Ruleset {
+    fn camel_cards(card_order: &str, wildcards: Vec<Wildcard>) -> Self {
+        Self {
+            card_order: card_order.chars().collect(),
+            hand_size: 5,
+            wildcards,
+            categories: HandType::ALL
+                .iter()
+                .map(|t| Category::new(t.name(), t.groups()))
+                .collect(),
+        }
+    }
+
+    /// Part 1: every card is itself, J is a jack.
+    fn standard() -> Self {
+        Self::camel_cards("23456789TJQKA", Vec::new())
+    }
+
+    /// Part 2: J is the weakest card but acts as whatever makes the best hand.
+    fn jokers() -> Self {
+        Self::camel_cards(
+            "J23456789TQKA",
+            vec![Wildcard {
+                symbol: 'J',
+                substitutes: None,
+            }],
+        )
+    }
+
+    fn parse(input: &str) -> Self {
+        let mut ruleset = Self {
+            card_order: Vec::new(),
+            hand_size: 5,
+            wildcards: Vec::new(),
+            categories: Vec::new(),
         };
 
-        match hand1_rank.cmp(&hand2_rank) {
-            cmp::Ordering::Less => cmp::Ordering::Less,
-            cmp::Ordering::Greater => cmp::Ordering::Greater,
-            cmp::Ordering::Equal => {
-                if compare_equal_hands(hand1, hand2, &card_ranking) {
-                    return cmp::Ordering::Greater;
+        for line in input
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        {
+            let (setting, value) = line
+                .split_once(':')
+                .unwrap_or_else(|| panic!("Expected `setting: value`, got: {}", line));
+            let value = value.trim();
+            match setting.trim() {
+                "cards" => ruleset.card_order = value.chars().collect(),
+                "hand size" => {
+                    ruleset.hand_size = value.parse().expect("hand size should be a number")
                 }
-                cmp::Ordering::Less
+                "wild" => {
+                    let mut parts = value.split_whitespace();
+                    let symbol = parts.next().and_then(|s| s.chars().exactly_one().ok());
+                    ruleset.wildcards.push(Wildcard {
+                        symbol: symbol.unwrap_or_else(|| {
+                            panic!("Expected a single wildcard, got: {}", value)
+                        }),
+                        substitutes: parts.next().map(|cards| cards.chars().collect()),
+                    });
+                }
+                "category" => {
+                    let groups = value
+                        .split_whitespace()
+                        .rev()
+                        .map_while(|g| g.parse::<usize>().ok())
+                        .collect::<Vec<_>>();
+                    let name = value
+                        .split_whitespace()
+                        .dropping_back(groups.len())
+                        .join(" ");
+                    ruleset.categories.push(Category {
+                        name,
+                        groups: groups
+                            .into_iter()
+                            .sorted_unstable_by(|a, b| b.cmp(a))
+                            .collect(),
+                    });
+                }
+                _ => panic!("Unknown setting: {}", setting),
             }
         }
-    });
 
-    let mut total = 0;
-    hands
-        .iter()
-        .rev()
-        .enumerate()
-        .for_each(|(i, (hand, rank))| {
-            let winnings = rank * (i + 1);
-            total += winnings;
-        });
+        assert!(!ruleset.card_order.is_empty(), "ruleset has no cards");
+        assert!(
+            ruleset.card_order.iter().all_unique(),
+            "ruleset lists a card twice"
+        );
+        assert!(!ruleset.categories.is_empty(), "ruleset has no categories");
+        for wildcard in &ruleset.wildcards {
+            assert!(
+                ruleset.card_order.contains(&wildcard.symbol),
+                "wildcard {} is missing from the card order",
+                wildcard.symbol
+            );
+        }
 
-    println!("{:?}", total);
-}
+        ruleset
+    }
 
-// J cards can pretend to be whatever card is best for the purpose of determining hand type; for example, QJJQ2 is now considered four of a kind. However, for the purpose of breaking ties between two hands of the same type, J is always treated as J, not the card it's pretending to be: JKKK2 is weaker than QQQQ2 because J is weaker than Q.
-fn try_with_every_joker(hand: &Vec<char>, fn_to_try: fn(&Vec<char>) -> bool) -> bool {
-    let hand = hand.clone();
-    let possible_chars = hand
-        .iter()
-        .filter(|&&card| card != 'J')
-        .collect::<Vec<&char>>();
-
-    for (i, card) in hand.iter().enumerate() {
-        if card == &'J' {
-            for &possible_char in possible_chars.iter() {
-                // replace all J with the possible char
-                let hand_copy = hand.iter().collect::<String>();
-                let hand_copy = hand_copy.replace('J', &possible_char.to_string());
-                let hand_copy = hand_copy.chars().collect::<Vec<char>>();
-                if fn_to_try(&hand_copy) {
-                    return true;
+    fn strength(&self, card: char) -> usize {
+        self.card_order
+            .iter()
+            .position(|&c| c == card)
+            .unwrap_or_else(|| panic!("Unknown card: {}", card))
+    }
+
+    fn wildcard(&self, card: char) -> Option<&Wildcard> {
+        self.wildcards.iter().find(|w| w.symbol == card)
+    }
+
+    /// Whether hands can be classified by [`HandType::from_counts`]: five
+    /// cards, the standard categories and wildcards that can become
+    /// anything, where joining the largest group is always best.
+    fn is_camel_cards(&self) -> bool {
+        self.hand_size == 5
+            && self.wildcards.iter().all(|w| w.substitutes.is_none())
+            && self
+                .categories
+                .iter()
+                .map(|c| c.groups.as_slice())
+                .eq(HandType::ALL.iter().map(|t| t.groups()))
+    }
+
+    /// The strongest category the hand reaches.
+    fn classify(&self, cards: &[char]) -> Classification {
+        assert_eq!(
+            cards.len(),
+            self.hand_size,
+            "hand {} should have {} cards",
+            cards.iter().collect::<String>(),
+            self.hand_size
+        );
+
+        if self.is_camel_cards() {
+            self.classify_camel_cards(cards)
+        } else {
+            self.search(cards)
+        }
+    }
+
+    /// Classifies a hand in one pass over its cards, with every wildcard
+    /// played as the card the hand holds most of.
+    fn classify_camel_cards(&self, cards: &[char]) -> Classification {
+        let mut counts = vec![0; self.card_order.len()];
+        let mut jokers = 0;
+        for &card in cards {
+            match self.wildcard(card) {
+                Some(_) => jokers += 1,
+                None => counts[self.strength(card)] += 1,
+            }
+        }
+        let category = HandType::from_counts(&counts, jokers) as usize;
+
+        // with no other cards in the hand, the wildcards all become the strongest card
+        let target = (0..counts.len())
+            .filter(|&i| self.wildcard(self.card_order[i]).is_none())
+            .max_by_key(|&i| (counts[i], i))
+            .map(|i| self.card_order[i]);
+        let played = cards
+            .iter()
+            .map(|&card| match self.wildcard(card) {
+                Some(_) => target.unwrap_or(card),
+                None => card,
+            })
+            .collect();
+
+        Classification { category, played }
+    }
+
+    /// Classifies a hand by trying every way its wildcards could be played.
+    fn search(&self, cards: &[char]) -> Classification {
+        let mut counts = vec![0; self.card_order.len()];
+        let mut wild = Vec::new();
+        for &card in cards {
+            match self.wildcard(card) {
+                Some(wildcard) => wild.push(wildcard),
+                None => counts[self.strength(card)] += 1,
+            }
+        }
+        if wild.is_empty() {
+            return Classification {
+                category: self.category_of(&counts),
+                played: cards.to_vec(),
+            };
+        }
+
+        // a wildcard only needs to try joining a card already in the hand or becoming a
+        // card nobody holds. Fresh cards some wildcard is restricted to are all worth
+        // trying, but any other fresh cards are interchangeable, so `wild.len()` of them do
+        let restricted = self
+            .wildcards
+            .iter()
+            .filter_map(|w| w.substitutes.as_ref())
+            .flatten()
+            .collect::<Vec<_>>();
+        let is_fresh = |i: usize| counts[i] == 0 && self.wildcard(self.card_order[i]).is_none();
+        let spare = (0..counts.len())
+            .filter(|&i| is_fresh(i) && !restricted.contains(&&self.card_order[i]))
+            .take(wild.len())
+            .collect::<Vec<_>>();
+        let options = wild
+            .iter()
+            .map(|wildcard| {
+                let options = (0..counts.len())
+                    .filter(|&i| {
+                        let card = self.card_order[i];
+                        let allowed = self.wildcard(card).is_none()
+                            && match &wildcard.substitutes {
+                                Some(allowed) => allowed.contains(&card),
+                                None => true,
+                            };
+                        let worth_trying = counts[i] > 0
+                            || spare.contains(&i)
+                            || (is_fresh(i) && restricted.contains(&&card));
+                        allowed && worth_trying
+                    })
+                    .collect::<Vec<_>>();
+
+                if options.is_empty() {
+                    // nothing it's allowed to become, so it plays as itself
+                    vec![self.strength(wildcard.symbol)]
+                } else {
+                    options
                 }
+            })
+            .collect::<Vec<_>>();
+        let strongest = self.categories.len() - 1;
+
+        let mut best = (0, Vec::new());
+        for choice in options.into_iter().multi_cartesian_product() {
+            for &i in &choice {
+                counts[i] += 1;
+            }
+            let category = self.category_of(&counts);
+            for &i in &choice {
+                counts[i] -= 1;
+            }
+
+            if best.1.is_empty() || category > best.0 {
+                best = (category, choice);
+            }
+            if best.0 == strongest {
+                break;
             }
         }
+
+        let (category, choice) = best;
+        let mut substitutes = choice.into_iter().map(|i| self.card_order[i]);
+        let played = cards
+            .iter()
+            .map(|&card| match self.wildcard(card) {
+                Some(_) => substitutes.next().unwrap(),
+                None => card,
+            })
+            .collect();
+
+        Classification { category, played }
     }
 
-    fn_to_try(&hand)
+    fn category_of(&self, counts: &[usize]) -> usize {
+        let groups = counts
+            .iter()
+            .copied()
+            .filter(|&c| c > 0)
+            .sorted_unstable_by(|a, b| b.cmp(a))
+            .collect::<Vec<_>>();
+
+        self.categories
+            .iter()
+            .rposition(|category| category.matches(&groups))
+            .unwrap_or(0)
+    }
 }
 
-fn is_five_of_a_kind(hand: &Vec<char>) -> bool {
-    let first_card = hand[0];
-    hand.iter().all(|&card| card == first_card)
+#[derive(Debug)]
+struct Hand {
+    cards: Vec<char>,
+    bid: usize,
+    classification: Classification,
+    /// Category first, then each card's strength in order, so ordering by key
+    /// ranks hands and breaks ties in one comparison.
+    key: (usize, Vec<usize>),
 }
 
-fn is_four_of_a_kind(hand: &Vec<char>) -> bool {
-    use std::collections::HashMap;
-    let mut counts = HashMap::new();
-    for &card in hand {
-        *counts.entry(card).or_insert(0) += 1;
-    }
+impl Hand {
+    fn parse(line: &str, ruleset: &Ruleset) -> Self {
+        let (cards, bid) = line.split_once(' ').unwrap();
+        let cards = cards.chars().collect::<Vec<_>>();
+        let classification = ruleset.classify(&cards);
+        let strengths = cards.iter().map(|&c| ruleset.strength(c)).collect();
 
-    counts.values().any(|&count| count == 4)
+        Self {
+            bid: bid.trim().parse().unwrap(),
+            key: (classification.category, strengths),
+            classification,
+            cards,
+        }
+    }
 }
 
-fn is_full_house(hand: &Vec<char>) -> bool {
-    use std::collections::HashMap;
-    let mut counts = HashMap::new();
-    for &card in hand {
-        *counts.entry(card).or_insert(0) += 1;
+fn main() {
+    println!("\n-- Advent of Code 2023 - Day 7 --");
+
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
+
+    let start = std::time::Instant::now();
+    part1(input);
+    part2(input);
+    println!("Finished after {}µs", start.elapsed().as_micros());
+
+    if let Some(path) = std::env::args().skip_while(|a| a != "--ruleset").nth(1) {
+        let ruleset = std::fs::read_to_string(&path).expect("failed to read ruleset");
+        let hands = match std::env::args().skip_while(|a| a != "--hands").nth(1) {
+            Some(path) => std::fs::read_to_string(path).expect("failed to read hands"),
+            None => input.to_string(),
+        };
+        let ruleset = Ruleset::parse(&ruleset);
+        println!("{}: {}", path, total_winnings(&hands, &ruleset));
+        if std::env::args().any(|a| a == "--explain") {
+            explain(&hands, &ruleset);
+        }
+    } else if std::env::args().any(|a| a == "--explain") {
+        explain(input, &Ruleset::jokers());
     }
-    counts.values().any(|&count| count == 3) && counts.values().any(|&count| count == 2)
 }
 
-fn is_three_of_a_kind(hand: &Vec<char>) -> bool {
-    use std::collections::HashMap;
-    let mut counts = HashMap::new();
-    for &card in hand {
-        *counts.entry(card).or_insert(0) += 1;
-    }
-    counts.values().any(|&count| count == 3)
+fn part1(input: &str) {
+    println!("Part 1: {}", total_winnings(input, &Ruleset::standard()));
 }
 
-fn is_two_pair(hand: &Vec<char>) -> bool {
-    use std::collections::HashMap;
-    let mut counts = HashMap::new();
-    for &card in hand {
-        *counts.entry(card).or_insert(0) += 1;
-    }
-    counts.values().filter(|&&count| count == 2).count() == 2
+fn part2(input: &str) {
+    println!("Part 2: {}", total_winnings(input, &Ruleset::jokers()));
 }
 
-fn is_one_pair(hand: &Vec<char>) -> bool {
-    use std::collections::HashMap;
-    let mut counts = HashMap::new();
-    for &card in hand {
-        *counts.entry(card).or_insert(0) += 1;
-    }
-    counts.values().any(|&count| count == 2)
+/// Hands from weakest to strongest, so a hand's rank is its index plus one.
+fn rank_hands(input: &str, ruleset: &Ruleset) -> Vec<Hand> {
+    let mut hands = input
+        .lines()
+        .map(|line| Hand::parse(line, ruleset))
+        .collect::<Vec<_>>();
+    hands.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+    hands
+}
+
+fn total_winnings(input: &str, ruleset: &Ruleset) -> usize {
+    rank_hands(input, ruleset)
+        .iter()
+        .enumerate()
+        .map(|(i, hand)| hand.bid * (i + 1))
+        .sum()
 }
 
-fn is_high_card(hand: &Vec<char>) -> bool {
-    use std::collections::HashMap;
-    let mut counts = HashMap::new();
-    for &card in hand {
-        *counts.entry(card).or_insert(0) += 1;
+/// Prints every hand with its rank, category, how its wildcards were played
+/// and what put it above the hand ranked just below it.
+fn explain(input: &str, ruleset: &Ruleset) {
+    let hands = rank_hands(input, ruleset);
+
+    for (i, hand) in hands.iter().enumerate() {
+        let cards = hand.cards.iter().collect::<String>();
+        let played = if hand.cards == hand.classification.played {
+            String::new()
+        } else {
+            format!(
+                "as {}",
+                hand.classification.played.iter().collect::<String>()
+            )
+        };
+
+        let reason = match i.checked_sub(1).map(|j| &hands[j]) {
+            None => "lowest hand".to_string(),
+            Some(below) if below.key.0 < hand.key.0 => format!(
+                "beats {} on category",
+                below.cards.iter().collect::<String>()
+            ),
+            Some(below) => match (0..hand.cards.len()).find(|&p| below.key.1[p] != hand.key.1[p]) {
+                Some(p) => format!(
+                    "beats {} at card {} ({} > {})",
+                    below.cards.iter().collect::<String>(),
+                    p + 1,
+                    hand.cards[p],
+                    below.cards[p]
+                ),
+                None => format!("ties {}", below.cards.iter().collect::<String>()),
+            },
+        };
+
+        println!(
+            "{:>5} {} {:<8} {:<16} bid {:>4}  {}",
+            i + 1,
+            cards,
+            played,
+            ruleset.categories[hand.classification.category].name,
+            hand.bid,
+            reason
+        );
     }
-    counts.values().all(|&count| count == 1)
 }
 
-fn compare_equal_hands(hand1: &[char], hand2: &[char], card_rankings: &[char]) -> bool {
-    for i in 0..5 {
-        let card1 = hand1[i];
-        let card2 = hand2[i];
-        let card1_rank = card_rankings
-            .iter()
-            .position(|&card| card == card1)
-            .unwrap();
-        let card2_rank = card_rankings
-            .iter()
-            .position(|&card| card == card2)
-            .unwrap();
-        match card1_rank.cmp(&card2_rank) {
-            cmp::Ordering::Less => return false,
-            cmp::Ordering::Greater => return true,
-            cmp::Ordering::Equal => continue,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_rulesets() {
+        assert_eq!(total_winnings(EXAMPLE_INPUT, &Ruleset::standard()), 6440);
+        assert_eq!(total_winnings(EXAMPLE_INPUT, &Ruleset::jokers()), 5905);
     }
 
-    false
-}
+    #[test]
+    fn test_parsed_ruleset() {
+        let ruleset = Ruleset::parse(
+            r#"
+            # six cards, two kinds of wildcard
+            cards: *J23456789TQKA
+            hand size: 6
+            wild: J
+            wild: * AK
+            category: High card
+            category: One pair 2
+            category: Two pair 2 2
+            category: Three of a kind 3
+            category: Four of a kind 4
+            category: Two triples 3 3
+            category: Six of a kind 6
+            "#,
+        );
 
-fn part2(input: &str) {
-    todo!()
+        let classify = |hand: &str| {
+            let classification = ruleset.classify(&hand.chars().collect::<Vec<_>>());
+            ruleset.categories[classification.category].name.as_str()
+        };
+        assert_eq!(classify("234567"), "High card");
+        assert_eq!(classify("22J345"), "Three of a kind");
+        // greedily growing the biggest group would give the weaker four of a kind
+        assert_eq!(classify("AA22JJ"), "Two triples");
+        assert_eq!(classify("AAAA2J"), "Four of a kind");
+        assert_eq!(classify("AAA22J"), "Two triples");
+        // `*` can't become a 2
+        assert_eq!(classify("22*345"), "One pair");
+        assert_eq!(classify("AA*345"), "Three of a kind");
+        assert_eq!(classify("JJJ**J"), "Six of a kind");
+
+        let played = ruleset
+            .classify(&"AA22JJ".chars().collect::<Vec<_>>())
+            .played;
+        assert_eq!(played.iter().collect::<String>(), "AA222A");
+    }
+
+    #[test]
+    fn test_camel_cards_fast_path() {
+        let ruleset = Ruleset::jokers();
+        assert!(ruleset.is_camel_cards());
+
+        // the count-based classifier agrees with trying every joker
+        for hand in std::iter::repeat_n("J23A".chars(), 5).multi_cartesian_product() {
+            let fast = ruleset.classify_camel_cards(&hand);
+            assert_eq!(fast.category, ruleset.search(&hand).category);
+            assert_eq!(
+                ruleset.search(&fast.played).category,
+                fast.category,
+                "{:?} played as {:?}",
+                hand,
+                fast.played
+            );
+        }
+        assert_eq!(
+            ruleset
+                .classify(&"JJJJJ".chars().collect::<Vec<_>>())
+                .played,
+            vec!['A'; 5]
+        );
+    }
 }
@@ -1,6 +1,9 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+};
 
-use num::integer::lcm;
+use num::integer::Integer;
 
 static EXAMPLE_INPUT: &str = r#"LR
 
@@ -13,93 +16,523 @@ static EXAMPLE_INPUT: &str = r#"LR
 22Z = (22B, 22B)
 XXX = (XXX, XXX)"#;
 
-fn main() {
-    println!("\n-- Advent of Code 2023 - Day 8 --");
+/// The desert map with every node interned to its index in `names`.
+#[derive(Debug)]
+struct Network<'a> {
+    steps: Vec<char>,
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, usize>,
+    left: Vec<usize>,
+    right: Vec<usize>,
+    /// `passes[k][node]` is where `node` ends up after following the whole
+    /// instruction string `2^k` times.
+    passes: Vec<Vec<usize>>,
+}
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
+impl<'a> Network<'a> {
+    fn parse(input: &'a str) -> Self {
+        let steps = input.lines().next().unwrap().chars().collect();
 
-    part1(input);
-    // part2(input);
-}
+        let mut edges = Vec::new();
+        for line in input.lines().skip(2) {
+            let mut parts = line.split(" = ");
+            let key = parts.next().unwrap();
+            let value = parts.next().unwrap();
 
-fn part1(input: &str) {
-    let mut map: HashMap<&str, (&str, &str)> = HashMap::new();
+            let mut value_parts = value.split(", ");
+            let left = value_parts
+                .next()
+                .unwrap()
+                .trim_matches('(')
+                .trim_matches(')');
+            let right = value_parts
+                .next()
+                .unwrap()
+                .trim_matches('(')
+                .trim_matches(')');
 
-    let steps = input.lines().next().unwrap();
-    for line in input.lines().skip(2) {
-        let mut parts = line.split(" = ");
-        let key = parts.next().unwrap();
-        let value = parts.next().unwrap();
+            edges.push((key, left, right));
+        }
 
-        let mut value_parts = value.split(", ");
-        let left = value_parts
-            .next()
-            .unwrap()
-            .trim_matches('(')
-            .trim_matches(')');
-        let right = value_parts
-            .next()
-            .unwrap()
-            .trim_matches('(')
-            .trim_matches(')');
-
-        map.insert(key, (left, right));
-        // println!("{}: ({}, {})", key, left, right);
-    }
-    // let start = "AAA";
-    // let goal = "ZZZ";
-
-    // println!("{:?}, len {}", steps, steps.len());
-    // println!("{:?}", map);
-    // println!("{:?}", start);
-    // println!("{:?}", goal);
-
-    // let mut current = start;
-    // let mut n_steps = 0;
-    // while current != goal {
-    //     let step = steps.chars().nth(n_steps % steps.len()).unwrap();
-
-    //     current = match step {
-    //         'L' => map.get(current).unwrap().0,
-    //         'R' => map.get(current).unwrap().1,
-    //         _ => panic!("Unknown step: {}", step),
-    //     };
-    //     n_steps += 1;
-    // }
-
-    // println!("Part 1 - Steps: {}", n_steps);
-
-    let paths_ending_with_a = map
-        .iter()
-        .filter(|(key, _)| key.ends_with('A'))
-        .map(|(key, _)| *key)
-        .collect::<Vec<_>>();
+        let names = edges.iter().map(|&(key, _, _)| key).collect::<Vec<_>>();
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(id, &name)| (name, id))
+            .collect::<HashMap<_, _>>();
+        let (left, right) = edges
+            .iter()
+            .map(|(_, left, right)| (ids[left], ids[right]))
+            .unzip();
+
+        let mut network = Self {
+            steps,
+            names,
+            ids,
+            left,
+            right,
+            passes: Vec::new(),
+        };
+        network.build_passes();
+        network
+    }
+
+    fn build_passes(&mut self) {
+        let pass = (0..self.names.len())
+            .map(|node| (0..self.steps.len()).fold(node, |node, n| self.step(node, n)))
+            .collect::<Vec<_>>();
+
+        self.passes = vec![pass];
+        for _ in 1..u64::BITS {
+            let last = self.passes.last().unwrap();
+            let doubled = last.iter().map(|&node| last[node]).collect();
+            self.passes.push(doubled);
+        }
+    }
 
-    println!("{:?}", paths_ending_with_a);
+    fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    /// Node reached from `current` when taking the `n_steps`:th step.
+    fn step(&self, current: usize, n_steps: usize) -> usize {
+        let step = self.steps[n_steps % self.steps.len()];
+        match step {
+            'L' => self.left[current],
+            'R' => self.right[current],
+            _ => panic!("Unknown step: {}", step),
+        }
+    }
+
+    /// Node reached from `start` after `n_steps` steps, jumping whole
+    /// instruction strings at a time by binary lifting.
+    fn position_after(&self, start: usize, n_steps: u64) -> usize {
+        let len = self.steps.len() as u64;
+        let (full_passes, remaining) = (n_steps / len, n_steps % len);
+
+        let mut current = start;
+        for (k, pass) in self.passes.iter().enumerate() {
+            if full_passes >> k & 1 == 1 {
+                current = pass[current];
+            }
+        }
 
-    let mut cycles = vec![];
-    for path in paths_ending_with_a {
-        let mut current = path;
+        (0..remaining as usize).fold(current, |node, n| self.step(node, n))
+    }
+
+    /// Walks from `start` until a (node, instruction index) state repeats,
+    /// noting every step count at which a goal node was reached.
+    fn trace(&self, start: usize, is_goal: impl Fn(&str) -> bool) -> Cycle {
+        let mut seen = vec![None; self.names.len() * self.steps.len()];
+        let mut goals = Vec::new();
+        let mut current = start;
         let mut n_steps = 0;
-        while !current.ends_with('Z') {
-            let step = steps.chars().nth(n_steps % steps.len()).unwrap();
 
-            current = match step {
-                'L' => map.get(current).unwrap().0,
-                'R' => map.get(current).unwrap().1,
-                _ => panic!("Unknown step: {}", step),
-            };
+        let cycle_start = loop {
+            let state = current * self.steps.len() + n_steps % self.steps.len();
+            if let Some(first_seen) = seen[state] {
+                break first_seen;
+            }
+            seen[state] = Some(n_steps);
+
+            if is_goal(self.names[current]) {
+                goals.push(n_steps as u64);
+            }
+            current = self.step(current, n_steps);
             n_steps += 1;
+        };
+
+        let cycle_start = cycle_start as u64;
+        let (cyclic, transient) = goals.into_iter().partition(|&g| g >= cycle_start);
+        Cycle {
+            start: cycle_start,
+            period: n_steps as u64 - cycle_start,
+            transient,
+            cyclic,
+        }
+    }
+
+    /// Graphviz DOT drawing of the network with `..A` nodes green and `..Z`
+    /// nodes red. With `color_cycles`, the nodes and edges of the loop each
+    /// ghost settles into are outlined in a colour of its own.
+    fn to_dot(&self, color_cycles: bool) -> String {
+        const PALETTE: [&str; 8] = [
+            "blue",
+            "darkorange",
+            "purple",
+            "teal",
+            "magenta",
+            "brown",
+            "gold",
+            "navy",
+        ];
+
+        // colours of the ghost loops using each node and each (node, direction) edge
+        let mut node_colors: HashMap<usize, Vec<&str>> = HashMap::new();
+        let mut edge_colors: HashMap<(usize, char), Vec<&str>> = HashMap::new();
+        if color_cycles {
+            for (ghost, start) in self.ghost_starts().enumerate() {
+                let color = PALETTE[ghost % PALETTE.len()];
+                let cycle = self.trace(start, |_| false);
+
+                let mut current = self.position_after(start, cycle.start);
+                for n in cycle.start..cycle.start + cycle.period {
+                    let direction = self.steps[n as usize % self.steps.len()];
+                    node_colors.entry(current).or_default().push(color);
+                    edge_colors
+                        .entry((current, direction))
+                        .or_default()
+                        .push(color);
+                    current = self.step(current, n as usize);
+                }
+            }
+        }
+        let color_attr = |colors: Option<&Vec<&str>>| {
+            colors.map(|colors| {
+                let mut colors = colors.clone();
+                colors.sort_unstable();
+                colors.dedup();
+                format!("color=\"{}\", penwidth=2", colors.join(":"))
+            })
+        };
+
+        let mut dot = String::from("digraph network {\n    node [shape=circle];\n");
+        for (id, name) in self.names.iter().enumerate() {
+            let fill = if name.ends_with('A') {
+                Some("style=filled, fillcolor=palegreen".to_string())
+            } else if name.ends_with('Z') {
+                Some("style=filled, fillcolor=salmon".to_string())
+            } else {
+                None
+            };
+            let attrs = fill.into_iter().chain(color_attr(node_colors.get(&id)));
+            writeln!(
+                dot,
+                "    \"{}\" [{}];",
+                name,
+                attrs.collect::<Vec<_>>().join(", ")
+            )
+            .unwrap();
+        }
+
+        for (id, name) in self.names.iter().enumerate() {
+            let (left, right) = (self.left[id], self.right[id]);
+            let edges = if left == right {
+                vec![(left, "LR")]
+            } else {
+                vec![(left, "L"), (right, "R")]
+            };
+
+            for (target, label) in edges {
+                let colors = label
+                    .chars()
+                    .filter_map(|direction| edge_colors.get(&(id, direction)))
+                    .flatten()
+                    .copied()
+                    .collect::<Vec<_>>();
+                let attrs = std::iter::once(format!("label=\"{}\"", label))
+                    .chain(color_attr((!colors.is_empty()).then_some(&colors)));
+                writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\" [{}];",
+                    name,
+                    self.names[target],
+                    attrs.collect::<Vec<_>>().join(", ")
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// Every node a ghost starts on.
+    fn ghost_starts(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.names.len()).filter(|&id| self.names[id].ends_with('A'))
+    }
+}
+
+/// Where a walk ends up looping and when it's on a goal node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cycle {
+    /// Step count at which the loop is entered.
+    start: u64,
+    period: u64,
+    /// Goal hits before the loop, each happening once.
+    transient: Vec<u64>,
+    /// Goal hits in the first pass of the loop, repeating every `period` steps.
+    cyclic: Vec<u64>,
+}
+
+impl Cycle {
+    fn first_goal(&self) -> Option<u64> {
+        self.transient.iter().chain(&self.cyclic).copied().min()
+    }
+
+    fn is_goal(&self, n_steps: u64) -> bool {
+        if n_steps < self.start {
+            self.transient.contains(&n_steps)
+        } else {
+            // past the start of the loop, matching a hit's residue means it's at or after that hit
+            self.cyclic
+                .iter()
+                .any(|&g| n_steps.abs_diff(g).is_multiple_of(self.period))
+        }
+    }
+
+    /// Whether the goal is only ever hit at multiples of the period, which is
+    /// what taking the LCM of the first hits assumes.
+    fn is_lcm_friendly(&self) -> bool {
+        self.transient.is_empty() && self.cyclic == [self.period]
+    }
+}
+
+/// The first common goal is too far away to compute in 128 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Overflow;
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the step count doesn't fit in 128 bits")
+    }
+}
+
+/// First step count at which every walk is on a goal node at once.
+///
+/// If every walk only hits its goal at multiples of its period, that's the LCM
+/// of the periods. Otherwise, step counts before every walk has entered its
+/// loop are checked one by one, and after that each walk's goal hits are
+/// residues modulo its period, combined with the Chinese remainder theorem.
+///
+/// With no walks at all there's nothing to line up, so that's `None` too.
+/// The answer can easily outgrow a `u64` with a few large periods, so it's
+/// worked out in `i128` and any overflow is reported rather than wrapped.
+fn first_common_goal(cycles: &[Cycle]) -> Result<Option<u128>, Overflow> {
+    if cycles.is_empty() {
+        return Ok(None);
+    }
+    if cycles.iter().all(Cycle::is_lcm_friendly) {
+        let lcm = cycles.iter().try_fold(1_i128, |acc, c| {
+            let period = c.period as i128;
+            (acc / acc.gcd(&period)).checked_mul(period).ok_or(Overflow)
+        })?;
+        return Ok(Some(lcm as u128));
+    }
+
+    let looping_from = cycles.iter().map(|c| c.start).max().unwrap_or(0);
+    if let Some(n_steps) = (0..looping_from).find(|&n| cycles.iter().all(|c| c.is_goal(n))) {
+        return Ok(Some(n_steps as u128));
+    }
+
+    // one congruence per combination of goal hits, so this grows with the
+    // product of the number of hits in each loop
+    let mut congruences = vec![(0_i128, 1_i128)];
+    for cycle in cycles {
+        let period = cycle.period as i128;
+        let mut combined = Vec::new();
+        for &congruence in &congruences {
+            for &g in &cycle.cyclic {
+                combined.extend(combine(congruence, (g as i128 % period, period))?);
+            }
+        }
+        combined.sort_unstable();
+        combined.dedup();
+        congruences = combined;
+    }
+
+    let from = looping_from as i128;
+    congruences
+        .into_iter()
+        .map(|(residue, modulus)| {
+            // smallest value >= looping_from with this residue
+            from.checked_add((residue - from).rem_euclid(modulus))
+                .map(|n| n as u128)
+                .ok_or(Overflow)
+        })
+        .try_fold(None, |best: Option<u128>, n| {
+            let n = n?;
+            Ok(Some(best.map_or(n, |best| best.min(n))))
+        })
+}
+
+/// Solves `x ≡ a (mod m)` and `x ≡ b (mod n)` for moduli that don't have to
+/// be coprime, giving `x ≡ c (mod lcm(m, n))`, or `None` if there's no
+/// solution.
+fn combine((a, m): (i128, i128), (b, n): (i128, i128)) -> Result<Option<(i128, i128)>, Overflow> {
+    let gcd = m.extended_gcd(&n);
+    if (b - a) % gcd.gcd != 0 {
+        return Ok(None);
+    }
+
+    let modulus = (m / gcd.gcd).checked_mul(n).ok_or(Overflow)?;
+    let step = n / gcd.gcd;
+    let k = ((b - a) / gcd.gcd)
+        .rem_euclid(step)
+        .checked_mul(gcd.x.rem_euclid(step))
+        .ok_or(Overflow)?
+        .rem_euclid(step);
+    // `m * k` is below `modulus`, which fits
+    Ok(Some(((a + m * k).rem_euclid(modulus), modulus)))
+}
+
+fn main() {
+    println!("\n-- Advent of Code 2023 - Day 8 --");
+
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
+    let network = Network::parse(input);
+
+    part1(&network);
+    part2(&network);
+
+    if let Some(path) = std::env::args().skip_while(|a| a != "--dot").nth(1) {
+        export_dot(&network, &path);
+    }
+
+    if let Some(n_steps) = std::env::args().skip_while(|a| a != "--after").nth(1) {
+        let n_steps = n_steps.parse().expect("step count should be a number");
+        for start in network.ghost_starts() {
+            let end = network.position_after(start, n_steps);
+            println!(
+                "{} after {} steps: {}",
+                network.names[start], n_steps, network.names[end]
+            );
         }
+    }
+}
+
+fn export_dot(network: &Network, path: &str) {
+    let color_cycles = std::env::args().any(|a| a == "--cycles");
+    std::fs::write(path, network.to_dot(color_cycles)).expect("failed to write DOT file");
+    println!("Wrote network to {}", path);
+}
 
-        cycles.push(n_steps);
+fn part1(network: &Network) {
+    let Some(start) = network.id("AAA") else {
+        println!("Part 1: no AAA node");
+        return;
+    };
+
+    match network.trace(start, |node| node == "ZZZ").first_goal() {
+        Some(n_steps) => println!("Part 1: {}", n_steps),
+        None => println!("Part 1: ZZZ is unreachable from AAA"),
     }
+}
 
-    let lcm = cycles.iter().fold(1, |acc, x| lcm(acc, *x));
-    println!("Part 2 - Steps: {}", lcm);
+fn part2(network: &Network) {
+    let cycles = network
+        .ghost_starts()
+        .map(|start| network.trace(start, |node| node.ends_with('Z')))
+        .collect::<Vec<_>>();
+    if cycles.is_empty() {
+        println!("Part 2: no nodes ending in A");
+        return;
+    }
+
+    match first_common_goal(&cycles) {
+        Ok(Some(n_steps)) => println!("Part 2: {}", n_steps),
+        Ok(None) => println!("Part 2: the ghosts never all stand on a Z node at once"),
+        Err(e) => println!("Part 2: {}", e),
+    }
 }
 
-fn part2(input: &str) {
-    todo!()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ghost_cycles(input: &str) -> Vec<Cycle> {
+        let network = Network::parse(input);
+        network
+            .ghost_starts()
+            .map(|start| network.trace(start, |node| node.ends_with('Z')))
+            .collect()
+    }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(first_common_goal(&ghost_cycles(EXAMPLE_INPUT)), Ok(Some(6)));
+    }
+
+    #[test]
+    fn test_offset_cycles() {
+        // 1A hits a Z after 1, 4, 7, ... steps and 2A after 2, 4, 6, ..., so
+        // the LCM of the first hits (2) is wrong
+        let input = "L\n\n1A = (1Z, 1Z)\n1Z = (1B, 1B)\n1B = (1C, 1C)\n1C = (1Z, 1Z)\n2A = (2B, 2B)\n2B = (2Z, 2Z)\n2Z = (2B, 2B)";
+        let cycles = ghost_cycles(input);
+
+        assert_eq!(
+            cycles[0],
+            Cycle {
+                start: 1,
+                period: 3,
+                transient: vec![],
+                cyclic: vec![1]
+            }
+        );
+        assert_eq!(first_common_goal(&cycles), Ok(Some(4)));
+    }
+
+    #[test]
+    fn test_unreachable() {
+        // odd and even step counts never line up
+        let input = "L\n\n1A = (1Z, 1Z)\n1Z = (1B, 1B)\n1B = (1Z, 1Z)\n2A = (2B, 2B)\n2B = (2Z, 2Z)\n2Z = (2B, 2B)";
+
+        assert_eq!(first_common_goal(&ghost_cycles(input)), Ok(None));
+
+        // no ghosts to walk
+        let input = "L\n\nBBB = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)";
+        assert!(ghost_cycles(input).is_empty());
+        assert_eq!(first_common_goal(&[]), Ok(None));
+    }
+
+    #[test]
+    fn test_beyond_u64() {
+        let cycle = |period: u64, hit: u64| Cycle {
+            start: 0,
+            period,
+            transient: vec![],
+            cyclic: vec![hit],
+        };
+
+        let periods = [10007, 10009, 10037, 10039, 10061];
+        let cycles = periods.map(|p| cycle(p, p));
+        assert_eq!(first_common_goal(&cycles), Ok(Some(101538353409718995449)));
+
+        // hits off the period go through the CRT instead
+        let (p, q) = ((1 << 61) - 1, (1 << 31) - 1);
+        let n = first_common_goal(&[cycle(p, 1), cycle(q, 2)])
+            .unwrap()
+            .unwrap();
+        assert!(n > u64::MAX as u128);
+        assert_eq!((n % p as u128, n % q as u128), (1, 2));
+
+        // Mersenne primes, so the combined modulus needs 141 bits
+        let primes = [61, 31, 19, 17, 13].map(|bits| cycle((1 << bits) - 1, 1));
+        assert_eq!(first_common_goal(&primes), Err(Overflow));
+        let friendly = [61, 31, 19, 17, 13].map(|bits| cycle((1 << bits) - 1, (1 << bits) - 1));
+        assert_eq!(first_common_goal(&friendly), Err(Overflow));
+    }
+
+    #[test]
+    fn test_position_after() {
+        let network = Network::parse(EXAMPLE_INPUT);
+
+        for start in 0..network.names.len() {
+            let mut current = start;
+            for n_steps in 0..50 {
+                assert_eq!(network.position_after(start, n_steps), current);
+                current = network.step(current, n_steps as usize);
+            }
+        }
+
+        let ghost = network.id("22A").unwrap();
+        assert_eq!(
+            network.names[network.position_after(ghost, 3_000_000_000_000_000)],
+            "22Z"
+        );
+    }
 }
@@ -1,84 +1,230 @@
+use itertools::Itertools;
+use num::{BigInt, One, Zero};
 
 static EXAMPLE_INPUT: &str = r#"0 3 6 9 12 15
 1 3 6 10 15 21
 10 13 16 21 30 45"#;
 
-fn main() {
-    println!("\n-- Advent of Code 2023 - Day 9 --");
+/// An OASIS reading stored as its Newton forward differences at index 0, which
+/// pin down the polynomial through the readings and so its value at any index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Sequence {
+    /// `differences[k]` is the k:th difference of the first reading.
+    differences: Vec<BigInt>,
+    len: usize,
+}
 
-    // let input = EXAMPLE_INPUT;
-    let input = include_str!("input.txt");
+impl Sequence {
+    fn new(readings: &[i64]) -> Self {
+        // difference the readings in place; after pass k, index k holds the k:th difference
+        let mut differences = readings
+            .iter()
+            .map(|&n| BigInt::from(n))
+            .collect::<Vec<_>>();
+        for k in 1..differences.len() {
+            for i in (k..differences.len()).rev() {
+                differences[i] = &differences[i] - &differences[i - 1];
+            }
+        }
 
-    part1(input);
-    // part2(input);
-}
+        Self {
+            differences,
+            len: readings.len(),
+        }
+    }
 
-fn part1(input: &str) {
-    let numbers = input
-        .lines()
-        .map(|line| {
-            line.split_whitespace()
-                .map(|n| n.parse::<i64>().unwrap())
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-
-    let sum = numbers
-        .iter()
-        .map(|line| process_line(line.clone()))
-        .sum::<i64>();
+    fn parse(line: &str) -> Self {
+        let readings = line
+            .split_whitespace()
+            .map(|n| n.parse::<i64>().unwrap())
+            .collect::<Vec<_>>();
 
-    println!("Sum: {}", sum);
-}
+        Self::new(&readings)
+    }
 
-fn process_line(line: Vec<i64>) -> i64 {
-    let mut differences = expand_line(vec![line]);
+    /// Degree of the polynomial through the readings, or `None` if they're
+    /// all zero.
+    fn degree(&self) -> Option<usize> {
+        self.differences.iter().rposition(|d| !d.is_zero())
+    }
 
-    let diff_len = differences.len();
-    for i in 0..diff_len {
-        let mut curr = differences[diff_len - (i + 1)].clone();
+    /// Whether some row of differences is all zero, so the polynomial is
+    /// confirmed by readings it wasn't fitted to.
+    ///
+    /// Any n readings fit a polynomial of degree n - 1, so when that's what it
+    /// takes the readings never settle and extrapolating them is a guess.
+    fn reduces_to_zero(&self) -> bool {
+        match self.degree() {
+            Some(degree) => degree + 1 < self.len,
+            None => true,
+        }
+    }
 
-        if i == 0 {
-            curr.insert(0, 0);
-            differences[diff_len - (i + 1)] = curr;
-            continue;
+    /// Value of the sequence at `index`, where the readings sit at `0..len`.
+    /// Negative indices extrapolate backwards.
+    ///
+    /// Newton's forward formula: the sum over k of `C(index, k) * Δᵏ`.
+    fn value_at(&self, index: &BigInt) -> BigInt {
+        let mut binomial = BigInt::one();
+        let mut value = BigInt::zero();
+
+        for (k, difference) in self.differences.iter().enumerate() {
+            if k > 0 {
+                // C(x, k) = C(x, k - 1) * (x - k + 1) / k, which always divides exactly
+                binomial = binomial * (index - (k - 1)) / k;
+            }
+            value += &binomial * difference;
         }
 
-        let diff_below = differences[diff_len - i].clone();
-        let new_value = curr.first().unwrap() - diff_below.first().unwrap();
-        curr.insert(0, new_value);
-        differences[diff_len - (i + 1)] = curr;
+        value
     }
 
-    *differences[0].first().unwrap()
+    /// Value `steps` places after the last reading.
+    fn extrapolate_forwards(&self, steps: i64) -> BigInt {
+        // `steps` comes from the command line, so the index may not fit an i64
+        self.value_at(&(BigInt::from(self.len) - 1 + steps))
+    }
+
+    /// Value `steps` places before the first reading.
+    fn extrapolate_backwards(&self, steps: i64) -> BigInt {
+        self.value_at(&-BigInt::from(steps))
+    }
 }
 
-fn expand_line(lines: Vec<Vec<i64>>) -> Vec<Vec<i64>> {
-    if lines
-        .last()
-        .iter()
-        .all(|diffs| diffs.iter().all(|n| *n == 0))
-    {
-        return lines;
+fn main() {
+    println!("\n-- Advent of Code 2023 - Day 9 --");
+
+    let input = if std::env::args().any(|a| a == "--example") {
+        EXAMPLE_INPUT
+    } else {
+        include_str!("input.txt")
+    };
+    let sequences = input.lines().map(Sequence::parse).collect::<Vec<_>>();
+    report(&sequences);
+
+    part1(&sequences);
+    part2(&sequences);
+
+    if let Some(steps) = std::env::args().skip_while(|a| a != "--steps").nth(1) {
+        let steps = steps.parse().expect("steps should be a number");
+        let forwards = sequences
+            .iter()
+            .map(|s| s.extrapolate_forwards(steps))
+            .sum::<BigInt>();
+        let backwards = sequences
+            .iter()
+            .map(|s| s.extrapolate_backwards(steps))
+            .sum::<BigInt>();
+        println!("{} steps forwards: {}", steps, forwards);
+        println!("{} steps backwards: {}", steps, backwards);
     }
+}
 
-    let current_line = lines.last().unwrap().clone();
-    let mut diffs = vec![];
+/// Warns about readings that never reduce to zero and, with `--degrees`,
+/// prints how many sequences have each degree.
+fn report(sequences: &[Sequence]) {
+    for (i, sequence) in sequences.iter().enumerate() {
+        if !sequence.reduces_to_zero() {
+            println!(
+                "Warning: line {}: differences never reach zero within {} readings, extrapolation is a guess",
+                i + 1,
+                sequence.len
+            );
+        }
+    }
 
-    for i in 0..current_line.len() {
-        if i == 0 {
-            diffs.push(0);
-        } else {
-            diffs.push(current_line[i] - current_line[i - 1]);
+    if std::env::args().any(|a| a == "--degrees") {
+        let degrees = sequences.iter().map(Sequence::degree).counts();
+        for (degree, count) in degrees.into_iter().sorted() {
+            match degree {
+                Some(degree) => println!("Degree {}: {} sequences", degree, count),
+                None => println!("All zero: {} sequences", count),
+            }
         }
     }
+}
 
-    let mut new_lines = lines.clone();
-    new_lines.push(diffs.iter().skip(1).copied().collect::<Vec<_>>());
+fn part1(sequences: &[Sequence]) {
+    let sum = sequences
+        .iter()
+        .map(|s| s.extrapolate_forwards(1))
+        .sum::<BigInt>();
 
-    expand_line(new_lines)
+    println!("Part 1: {}", sum);
 }
 
-fn _part2(_input: &str) {
-    todo!()
+fn part2(sequences: &[Sequence]) {
+    let sum = sequences
+        .iter()
+        .map(|s| s.extrapolate_backwards(1))
+        .sum::<BigInt>();
+
+    println!("Part 2: {}", sum);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degree() {
+        let sequences = EXAMPLE_INPUT
+            .lines()
+            .map(Sequence::parse)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sequences.iter().map(Sequence::degree).collect::<Vec<_>>(),
+            vec![Some(1), Some(2), Some(3)]
+        );
+        assert!(sequences.iter().all(Sequence::reduces_to_zero));
+
+        assert_eq!(Sequence::new(&[0, 0, 0]).degree(), None);
+        assert!(Sequence::new(&[0, 0, 0]).reduces_to_zero());
+        assert!(Sequence::new(&[7, 7]).reduces_to_zero());
+
+        // powers of two never settle, their differences are powers of two too
+        let doubling = Sequence::new(&[1, 2, 4, 8, 16]);
+        assert_eq!(doubling.degree(), Some(4));
+        assert!(!doubling.reduces_to_zero());
+        assert!(!Sequence::new(&[5]).reduces_to_zero());
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        let sequences = EXAMPLE_INPUT
+            .lines()
+            .map(Sequence::parse)
+            .collect::<Vec<_>>();
+        let forwards = sequences
+            .iter()
+            .map(|s| s.extrapolate_forwards(1))
+            .collect::<Vec<_>>();
+        let backwards = sequences
+            .iter()
+            .map(|s| s.extrapolate_backwards(1))
+            .collect::<Vec<_>>();
+        assert_eq!(forwards, [18, 28, 68].map(BigInt::from));
+        assert_eq!(backwards, [-3, 0, 5].map(BigInt::from));
+
+        // indices past the ends of i64 still extrapolate
+        let sequence = &sequences[0];
+        assert_eq!(
+            sequence.extrapolate_forwards(i64::MAX),
+            (BigInt::from(i64::MAX) + 5) * 3
+        );
+        assert_eq!(
+            sequence.extrapolate_backwards(i64::MIN),
+            BigInt::from(i64::MIN) * -3
+        );
+    }
+
+    #[test]
+    fn test_extrapolate_without_overflow() {
+        let sequence = Sequence::new(&[i64::MAX - 2, i64::MAX - 1, i64::MAX]);
+        assert_eq!(sequence.extrapolate_forwards(2), BigInt::from(i64::MAX) + 2);
+        assert_eq!(
+            sequence.extrapolate_backwards(1),
+            BigInt::from(i64::MAX - 3)
+        );
+    }
 }